@@ -0,0 +1,98 @@
+//! A minimal command line front end to `BlockadeHandler`.  Results are printed as JSON.
+//!
+//! ```text
+//! blockade-rs [--host URL] list
+//! blockade-rs [--host URL] kill <blockade> <container>
+//! blockade-rs [--host URL] partition <blockade> <c1,c2> <c3> ...
+//! blockade-rs [--host URL] heal <blockade>
+//! blockade-rs [--host URL] net <fast|flaky> <blockade>
+//! ```
+
+#![allow(clippy::needless_return)]
+
+extern crate blockade;
+extern crate serde;
+extern crate serde_json;
+
+use std::env;
+use std::process;
+
+use blockade::{BlockadeHandler, BlockadeNetStatus};
+use serde::Serialize;
+
+const USAGE: &str = "usage: blockade-rs [--host URL] <command> [args]
+
+commands:
+    list                              list the daemon's blockades
+    kill <blockade> <container>       kill one container
+    partition <blockade> <group>...   partition; each group is comma-separated containers
+    heal <blockade>                   remove all partitions
+    net <fast|flaky> <blockade>       set every container's network";
+
+const DEFAULT_HOST: &str = "http://127.0.0.1:5000";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut host = String::from(DEFAULT_HOST);
+    if args.len() >= 2 && args[0] == "--host" {
+        host = args.remove(1);
+        args.remove(0);
+    }
+    if args.is_empty() {
+        fail(USAGE);
+    }
+
+    // The handler starts out empty rather than failing when the daemon is down.
+    if let Err(e) = BlockadeHandler::ping(&host) {
+        fail(&e.to_string());
+    }
+    let mut handler = BlockadeHandler::new(&host);
+    let command = args.remove(0);
+    let result = match (command.as_str(), args.as_slice()) {
+        ("list", []) => print(&handler.blockades),
+        ("kill", [name, container]) => match handler.kill_container(name, container) {
+            Ok(state) => print(&state),
+            Err(e) => Err(e.to_string()),
+        },
+        ("partition", [name, groups @ ..]) if !groups.is_empty() => {
+            let partitions = groups
+                .iter()
+                .map(|g| g.split(',').map(String::from).collect())
+                .collect();
+            match handler.make_partitions(name, partitions) {
+                Ok(()) => print(&handler.state[name.as_str()]),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        ("heal", [name]) => match handler.heal_partitions(name) {
+            Ok(()) => print(&handler.state[name.as_str()]),
+            Err(e) => Err(e.to_string()),
+        },
+        ("net", [status, name]) => {
+            let result = match status.parse() {
+                Ok(BlockadeNetStatus::Fast) => handler.make_net_fast(name),
+                Ok(BlockadeNetStatus::Flaky) => handler.make_net_unreliable(name),
+                _ => fail(USAGE),
+            };
+            match result {
+                Ok(containers) => print(&containers),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+        _ => fail(USAGE),
+    };
+    if let Err(e) = result {
+        fail(&e);
+    }
+}
+
+fn print<T: Serialize>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", json);
+    return Ok(());
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
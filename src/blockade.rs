@@ -1,12 +1,40 @@
 use std::collections::HashMap;
-use std::{error, fmt};
+#[cfg(all(unix, feature = "unix-socket"))]
+use std::path::Path;
+use std::panic;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use std::{error, fmt, thread};
 
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json;
 
-use rand::{seq, thread_rng};
+use rand::{seq, thread_rng, Rng};
 use reqwest;
 
 use common::*;
+#[cfg(all(unix, feature = "unix-socket"))]
+use unix;
+
+/// Enters a `tracing` span for a daemon operation, lasting until the end of the enclosing
+/// block.  Expands to nothing without the `tracing` feature.
+macro_rules! operation_span {
+    ($op:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("blockade", operation = $op).entered();
+    };
+    ($op:expr, $name:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("blockade", operation = $op, blockade = $name).entered();
+    };
+    ($op:expr, $name:expr, $($fields:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("blockade", operation = $op, blockade = $name, $($fields)+)
+                .entered();
+    };
+}
 
 #[derive(Debug)]
 pub enum BlockadeError {
@@ -14,6 +42,16 @@ pub enum BlockadeError {
     ServerError(String),
     OtherError(String),
     JsonError(serde_json::Error),
+    /// A daemon response didn't parse; holds the parse error and the response text as sent.
+    BadResponse {
+        source: serde_json::Error,
+        raw: String,
+    },
+    EmptyBlockade(String),
+    /// The daemon answered 404; holds the daemon's response text.
+    NotFound(String),
+    /// A blockade of that name already exists; holds the daemon's response text.
+    Conflict(String),
 }
 
 impl fmt::Display for BlockadeError {
@@ -23,6 +61,15 @@ impl fmt::Display for BlockadeError {
             BlockadeError::OtherError(ref n) => write!(f, "Other error: {:?}", n),
             BlockadeError::ServerError(ref n) => write!(f, "Server error: {:?}", n),
             BlockadeError::JsonError(ref n) => write!(f, "JSON parsing error: {:?}", n),
+            BlockadeError::BadResponse {
+                ref source,
+                ref raw,
+            } => write!(f, "JSON parsing error: {:?} in response {:?}", source, raw),
+            BlockadeError::EmptyBlockade(ref n) => {
+                write!(f, "No containers to choose from: {:?}", n)
+            }
+            BlockadeError::NotFound(ref n) => write!(f, "Not found: {:?}", n),
+            BlockadeError::Conflict(ref n) => write!(f, "Conflict: {:?}", n),
         }
     }
 }
@@ -33,6 +80,19 @@ impl From<reqwest::Error> for BlockadeError {
     }
 }
 
+/// Messages become `OtherError`, so `Err("message".into())` works.
+impl<'a> From<&'a str> for BlockadeError {
+    fn from(message: &'a str) -> BlockadeError {
+        return BlockadeError::OtherError(message.into());
+    }
+}
+
+impl From<String> for BlockadeError {
+    fn from(message: String) -> BlockadeError {
+        return BlockadeError::OtherError(message);
+    }
+}
+
 impl From<serde_json::Error> for BlockadeError {
     fn from(error: serde_json::Error) -> BlockadeError {
         return BlockadeError::JsonError(error);
@@ -43,37 +103,415 @@ impl error::Error for BlockadeError {
     fn description(&self) -> &str {
         "Something went wrong with the blockade"
     }
-    fn cause(&self) -> Option<&error::Error> {
+    fn cause(&self) -> Option<&dyn error::Error> {
         return None;
     }
 }
 
-#[derive(Debug)]
+/// How long the `wait_for_*` helpers sleep between polls of the daemon.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Separate time limits for reads (`GET`s of the blockade list and states) and mutations
+/// (actions, network state and partition changes, setup and teardown), which can take much
+/// longer while the daemon rewrites iptables rules.  Each covers a whole request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeoutConfig {
+    pub connect: Duration,
+    pub read: Duration,
+    pub mutate: Duration,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        return TimeoutConfig {
+            connect: Duration::from_secs(5),
+            read: Duration::from_secs(10),
+            mutate: Duration::from_secs(120),
+        };
+    }
+}
+
+/// Credentials attached to every request sent to the blockade daemon.
+#[derive(Clone, PartialEq)]
+pub enum AuthScheme {
+    Bearer(String),
+    Basic { user: String, pass: String },
+}
+
+impl fmt::Debug for AuthScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AuthScheme::Bearer(_) => write!(f, "Bearer(<redacted>)"),
+            AuthScheme::Basic { ref user, .. } => {
+                write!(f, "Basic {{ user: {:?}, pass: <redacted> }}", user)
+            }
+        }
+    }
+}
+
+/// Callback receiving `(url, request body, response status, response body)` for every
+/// exchange with the daemon.
+pub type BlockadeObserver = dyn Fn(&str, &str, u16, &str) + Send + Sync;
+
+#[derive(Clone)]
+struct Observer(Arc<BlockadeObserver>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Observer")
+    }
+}
+
+/// Callback receiving `(blockade, container, change)` for every container whose status,
+/// network state or partition changed between two refreshes of the cached state.
+pub type BlockadeTransitionHook = dyn Fn(&str, &str, &ContainerChange) + Send + Sync;
+
+#[derive(Clone)]
+struct TransitionHook(Arc<BlockadeTransitionHook>);
+
+impl fmt::Debug for TransitionHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TransitionHook")
+    }
+}
+
+/// Carries requests to the daemon.  Handlers send over HTTP with reqwest by default; a
+/// custom transport (see `BlockadeHandler::with_transport`) can e.g. answer from canned
+/// responses in unit tests.  `request` is fully prepared, with the handler's `auth` and
+/// `headers` (so also a `with_user_agent` user agent) applied, and `body` is its JSON body,
+/// if any.  Returns the response status and body.  Settings that live in a reqwest client
+/// rather than the request, like the limits of `with_timeouts` or the roots of `with_tls`,
+/// only apply to the default transport; a custom one has to provide its own.  Requests are
+/// reqwest 0.9 types, so implementors need that version of reqwest.
+pub trait BlockadeTransport: Send + Sync {
+    fn send(
+        &self,
+        request: &reqwest::Request,
+        body: Option<&str>,
+    ) -> Result<(u16, String), BlockadeError>;
+}
+
+/// The default transport, sending each request with a reqwest client.
+#[derive(Clone, Debug)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    /// Used instead of `client` for non-`GET` requests when set; see
+    /// `BlockadeHandler::with_timeouts`.
+    mutation_client: Option<reqwest::Client>,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        return ReqwestTransport {
+            client,
+            mutation_client: None,
+        };
+    }
+}
+
+impl BlockadeTransport for ReqwestTransport {
+    fn send(
+        &self,
+        request: &reqwest::Request,
+        body: Option<&str>,
+    ) -> Result<(u16, String), BlockadeError> {
+        let client = match self.mutation_client {
+            Some(ref c) if request.method() != reqwest::Method::GET => c,
+            _ => &self.client,
+        };
+        let mut builder = client
+            .request(request.method().clone(), request.url().clone())
+            .headers(request.headers().clone());
+        if let Some(json) = body {
+            builder = builder.body(json.to_owned());
+        }
+        let mut res = builder.send()?;
+        let status = res.status();
+        return Ok((status.as_u16(), res.text()?));
+    }
+}
+
+#[derive(Clone)]
+struct Transport(Arc<dyn BlockadeTransport>);
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Transport")
+    }
+}
+
+/// Cloning a handler copies its cached state and config but shares the underlying HTTP
+/// client, so clones keep using the same connection pool.
+#[derive(Clone, Debug)]
 pub struct BlockadeHandler {
+    /// Prepares requests; the transport sends them.
     pub client: reqwest::Client,
     pub host: String,
+    pub base_path: String,
+    pub auth: Option<AuthScheme>,
+    /// Added to every request, e.g. by `with_headers`.
+    pub headers: reqwest::header::HeaderMap,
     pub blockades: Vec<String>,
     pub state: HashMap<String, BlockadeState>,
     pub config: HashMap<String, BlockadeConfig>,
+    /// Client-side container tags (e.g. roles), by blockade and then tag.  Never sent to the
+    /// daemon; see `tag_containers`.
+    pub tags: HashMap<String, HashMap<String, Vec<String>>>,
+    pub dry_run: bool,
+    /// The field of the `GET /blockade` response holding the blockade names.  Defaults to
+    /// `"blockades"`; some daemon forks use e.g. `"names"`.
+    pub list_key: String,
+    transport: Transport,
+    observer: Option<Observer>,
+    on_transition: Option<TransitionHook>,
 }
 
 impl BlockadeHandler {
     /// Make a new BlockadeHandler that uses a blockade instance
     /// started at "host".
     pub fn new(host: &str) -> Self {
-        let client = reqwest::Client::new();
-        let mut handler = BlockadeHandler {
-            client: client,
-            host: host.to_owned(),
+        return BlockadeHandler::with_base_path(host, "/blockade");
+    }
+
+    /// Make a new BlockadeHandler that talks to a blockade instance at "host" whose API
+    /// is mounted under "base_path" (e.g. "/chaos/api" behind a reverse proxy).
+    pub fn with_base_path(host: &str, base_path: &str) -> Self {
+        let mut handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, base_path);
+        handler.warm_up();
+        return handler;
+    }
+
+    /// Make a new BlockadeHandler that authenticates every request to "host" with the
+    /// given scheme.
+    pub fn with_auth(host: &str, auth: AuthScheme) -> Self {
+        let mut handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, "/blockade");
+        handler.auth = Some(auth);
+        handler.warm_up();
+        return handler;
+    }
+
+    /// Make a new BlockadeHandler for a daemon served over HTTPS whose certificate is signed
+    /// by `root_cert`, e.g. an internal CA.  The system roots are still trusted too.
+    pub fn with_tls(host: &str, root_cert: reqwest::Certificate) -> Result<Self, BlockadeError> {
+        let client = reqwest::Client::builder()
+            .add_root_certificate(root_cert)
+            .build()?;
+        return Ok(BlockadeHandler::with_client(host, client));
+    }
+
+    /// Make a new BlockadeHandler that identifies itself to "host" with the given
+    /// `User-Agent`, e.g. so chaos traffic stands out in the daemon's logs.
+    pub fn with_user_agent(host: &str, user_agent: &str) -> Result<Self, BlockadeError> {
+        let value = match reqwest::header::HeaderValue::from_str(user_agent) {
+            Ok(value) => value,
+            Err(_e) => {
+                return Err(BlockadeError::OtherError(format!(
+                    "invalid user agent: {:?}",
+                    user_agent
+                )))
+            }
+        };
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::USER_AGENT, value);
+        return BlockadeHandler::with_headers(host, headers);
+    }
+
+    /// Make a new BlockadeHandler that adds `headers` to every request to "host", e.g. an
+    /// `X-Tenant-Id` required by a gateway.  Auth is better set with `with_auth`.
+    pub fn with_headers(
+        host: &str,
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<Self, BlockadeError> {
+        let mut handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, "/blockade");
+        handler.headers = headers;
+        handler.warm_up();
+        return Ok(handler);
+    }
+
+    /// Make a new BlockadeHandler whose reads and mutations are held to the separate limits
+    /// in `timeouts`.  reqwest can't time individual requests, so this builds one client for
+    /// each kind of request.
+    pub fn with_timeouts(host: &str, timeouts: TimeoutConfig) -> Result<Self, BlockadeError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.read)
+            .build()?;
+        let mutation_client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.mutate)
+            .build()?;
+        let mut handler = BlockadeHandler::from_parts(client.clone(), host, "/blockade");
+        handler.transport = Transport(Arc::new(ReqwestTransport {
+            client,
+            mutation_client: Some(mutation_client),
+        }));
+        handler.warm_up();
+        return Ok(handler);
+    }
+
+    /// Make a new BlockadeHandler that sends requests to "host" with a preconfigured client,
+    /// e.g. one with tuned pool, proxy or TLS settings.
+    pub fn with_client(host: &str, client: reqwest::Client) -> Self {
+        let mut handler = BlockadeHandler::from_parts(client, host, "/blockade");
+        handler.warm_up();
+        return handler;
+    }
+
+    /// Make a new BlockadeHandler that keeps at most `max_idle` idle connections to "host"
+    /// open for reuse.  reqwest doesn't limit concurrent connections, so each parallel
+    /// operation (`fetch_state`, `kill_one_in_each`, clones used from other threads) opens
+    /// its own when none is idle; by default all of them are then kept.  For bursts of
+    /// parallel chaos, a limit around the number of concurrent workers reuses connections
+    /// without holding dozens of sockets open in between.
+    pub fn with_pool_max_idle(host: &str, max_idle: usize) -> Result<Self, BlockadeError> {
+        let client = reqwest::Client::builder()
+            .max_idle_per_host(max_idle)
+            .build()?;
+        return Ok(BlockadeHandler::with_client(host, client));
+    }
+
+    /// Make a new BlockadeHandler that talks to a daemon listening on the Unix domain socket
+    /// at "path" instead of a TCP port, e.g. a sidecar with no exposed port.  URLs seen by
+    /// an observer use the placeholder host `http://localhost`.
+    #[cfg(all(unix, feature = "unix-socket"))]
+    pub fn with_unix_socket<P: AsRef<Path>>(path: P) -> Self {
+        let mut handler =
+            BlockadeHandler::from_parts(reqwest::Client::new(), "http://localhost", "/blockade");
+        handler.transport = Transport(Arc::new(unix::UnixSocketTransport::new(path.as_ref())));
+        handler.warm_up();
+        return handler;
+    }
+
+    /// Make a new BlockadeHandler that sends every request for "host" through `transport`
+    /// instead of reqwest, e.g. a fake answering from canned responses in unit tests.
+    pub fn with_transport(host: &str, transport: Box<dyn BlockadeTransport>) -> Self {
+        let mut handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, "/blockade");
+        handler.transport = Transport(Arc::from(transport));
+        handler.warm_up();
+        return handler;
+    }
+
+    /// Make a handler in dry-run mode: requests are logged instead of sent and the daemon
+    /// is never contacted.  See `set_dry_run`.
+    pub fn dry_run(host: &str) -> Self {
+        let mut handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, "/blockade");
+        handler.dry_run = true;
+        return handler;
+    }
+
+    /// Checks that a blockade daemon is answering at "host" with a lightweight
+    /// `GET /blockade`, e.g. as a readiness probe before constructing a handler (which
+    /// otherwise starts with an empty state when the daemon is down).
+    pub fn ping(host: &str) -> Result<(), BlockadeError> {
+        let handler = BlockadeHandler::from_parts(reqwest::Client::new(), host, "/blockade");
+        let (status, text) = handler.send(reqwest::Method::GET, "", None)?;
+
+        debug!("Pinged server with status: {}", status);
+
+        if status.is_success() {
+            return Ok(());
+        } else {
+            return Err(BlockadeError::ServerError(text));
+        }
+    }
+
+    /// Assembles a handler without contacting the daemon.
+    fn from_parts(client: reqwest::Client, host: &str, base_path: &str) -> Self {
+        let transport = Transport(Arc::new(ReqwestTransport::new(client.clone())));
+        return BlockadeHandler {
+            client,
+            host: host.trim_end_matches('/').to_owned(),
+            base_path: normalize_base_path(base_path),
+            auth: None,
+            headers: reqwest::header::HeaderMap::new(),
             blockades: Vec::new(),
             state: HashMap::new(),
             config: HashMap::new(),
+            tags: HashMap::new(),
+            dry_run: false,
+            list_key: String::from("blockades"),
+            transport,
+            observer: None,
+            on_transition: None,
+        };
+    }
+
+    /// Finds out what the daemon supports.  Blockade has no version endpoint, so this
+    /// reads a `"version"` field from the blockade list if the daemon adds one, and probes
+    /// chaos mode with a `GET` on the first listed blockade's `chaos` route.  A daemon with
+    /// chaos answers it, or refuses it with a 4xx about chaos (e.g. that none is running);
+    /// one without answers 404.  Any other answer is an error, since it says nothing about
+    /// chaos.  Refreshes the blockade list.  Dry runs learn nothing.
+    pub fn get_daemon_info(&mut self) -> Result<DaemonInfo, BlockadeError> {
+        if self.dry_run {
+            return Ok(DaemonInfo::default());
+        }
+        let (status, raw_text) = self.send(reqwest::Method::GET, "", None)?;
+        if !status.is_success() {
+            return Err(BlockadeError::ServerError(raw_text));
+        }
+        self.blockades = parse_blockade_list(&raw_text, &self.list_key)?;
+        let version = match serde_json::from_str::<serde_json::Value>(&raw_text) {
+            Ok(listing) => listing
+                .get("version")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            Err(_e) => None,
         };
-        match handler.execute_list_blockades() {
+        let chaos = match self.blockades.first() {
+            Some(name) => {
+                let path = format!("/{}/chaos", name);
+                let (status, text) = self.send(reqwest::Method::GET, &path, None)?;
+                if status.is_success() {
+                    Some(true)
+                } else if status == reqwest::StatusCode::NOT_FOUND {
+                    Some(false)
+                } else if status.is_client_error() && text.to_lowercase().contains("chaos") {
+                    Some(true)
+                } else {
+                    return Err(BlockadeError::ServerError(text));
+                }
+            }
+            None => None,
+        };
+        return Ok(DaemonInfo { version, chaos });
+    }
+
+    /// Registers a callback that sees every request/response exchanged with the daemon,
+    /// e.g. for recording HTTP traffic in test reports.  Replaces any previous observer.
+    pub fn set_observer(&mut self, observer: Box<BlockadeObserver>) {
+        self.observer = Some(Observer(Arc::from(observer)));
+    }
+
+    /// Registers a callback told about each container that changed between the cached state
+    /// of a blockade and a fresh one, whenever the cache is refreshed (by `fetch_state` or
+    /// any other call that re-reads a blockade).  Nothing is reported for a blockade's first
+    /// fetch.  Replaces any previous callback.
+    pub fn set_on_transition(&mut self, hook: Box<BlockadeTransitionHook>) {
+        self.on_transition = Some(TransitionHook(Arc::from(hook)));
+    }
+
+    /// Toggles dry-run mode.  While enabled, every mutating request is logged at info level
+    /// with its URL and JSON body and treated as successful without being sent, and state
+    /// queries answer from the cache (or from the stored config for blockades started in
+    /// dry-run mode) instead of the daemon.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Sets the field of the `GET /blockade` response that lists the blockade names, for
+    /// daemon forks that don't use `"blockades"`.
+    pub fn set_list_key(&mut self, key: &str) {
+        self.list_key = key.into();
+    }
+
+    /// Best-effort initial sync of the blockade list and their states.
+    fn warm_up(&mut self) {
+        match self.execute_list_blockades() {
             Ok(_val) => {
-                for i in 0..handler.blockades.len() {
-                    let blockade_name = handler.blockades[i].to_owned();
-                    match handler.execute_get_blockade(&blockade_name) {
+                for i in 0..self.blockades.len() {
+                    let blockade_name = self.blockades[i].to_owned();
+                    match self.execute_get_blockade(&blockade_name) {
                         Ok(_val) => {}
                         Err(_e) => {}
                     }
@@ -81,7 +519,44 @@ impl BlockadeHandler {
             }
             Err(_e) => {}
         }
-        return handler;
+    }
+
+    /// The cached state of a blockade, or `None` if it was never fetched.  No request is made.
+    pub fn state_cached(&self, name: &str) -> Option<&BlockadeState> {
+        return self.state.get(name);
+    }
+
+    /// The sorted container names of a blockade from the cache, like `get_all_containers`
+    /// without the refresh.  `None` if the blockade's state was never fetched.
+    pub fn containers_cached(&self, name: &str) -> Option<Vec<String>> {
+        return self.state.get(name).map(|state| {
+            let mut containers: Vec<String> = state.containers.keys().cloned().collect();
+            containers.sort();
+            containers
+        });
+    }
+
+    /// Walks every cached container of every blockade as `(blockade, container, state)`.
+    /// Reads only the cache; call `fetch_state` first for fresh data.  Order is unspecified.
+    pub fn all_containers(&self) -> impl Iterator<Item = (&str, &str, &BlockadeContainerState)> {
+        return self.state.iter().flat_map(|(blockade, state)| {
+            state
+                .containers
+                .iter()
+                .map(move |(container, c)| (blockade.as_str(), container.as_str(), c))
+        });
+    }
+
+    /// Counts containers by status and partitions for every cached blockade, e.g. for a
+    /// dashboard.  Reads only the cache.
+    pub fn summary(&self) -> BlockadeSummary {
+        let mut summary = BlockadeSummary::default();
+        for (name, state) in self.state.iter() {
+            let counts = state.counts();
+            summary.partitions += counts.partitions;
+            summary.blockades.insert(name.clone(), counts);
+        }
+        return summary;
     }
 
     /// Returns all container names in default String order (lexicographical).
@@ -91,177 +566,1343 @@ impl BlockadeHandler {
             self.state[name]
                 .containers
                 .keys()
-                .map(|val: &String| val.clone())
+                .cloned()
                 .collect()
         } else {
             Vec::new()
         };
-        all_containers.sort();
-        return Ok(all_containers);
+        all_containers.sort();
+        return Ok(all_containers);
+    }
+
+    /// Refreshes the blockade's state and returns its containers with their states, ordered
+    /// by `key`, e.g. by IP address when balancing partitions.
+    pub fn get_containers_sorted_by(
+        &mut self,
+        name: &str,
+        key: ContainerSortKey,
+    ) -> Result<Vec<(String, BlockadeContainerState)>, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        let mut containers: Vec<(String, BlockadeContainerState)> =
+            state.containers.into_iter().collect();
+        containers.sort_by(|a, b| a.0.cmp(&b.0));
+        match key {
+            ContainerSortKey::Name => {}
+            ContainerSortKey::IpAddress => containers.sort_by_key(|c| c.1.ip_address),
+            ContainerSortKey::Status => containers.sort_by_key(|c| match c.1.status {
+                BlockadeContainerStatus::Up => 0,
+                BlockadeContainerStatus::Down => 1,
+                BlockadeContainerStatus::Missing => 2,
+            }),
+            ContainerSortKey::Partition => containers.sort_by_key(|c| c.1.partition),
+        }
+        return Ok(containers);
+    }
+
+    /// Refreshes the blockade's state and returns those of `expected_up` that aren't `Up`,
+    /// in the order given, e.g. to tell a spontaneous crash from the containers a test took
+    /// down itself.  A container the daemon no longer reports at all counts as down.
+    pub fn detect_unexpected_down(
+        &mut self,
+        name: &str,
+        expected_up: &[String],
+    ) -> Result<Vec<String>, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        return Ok(expected_up
+            .iter()
+            .filter(|c| match state.containers.get(*c) {
+                Some(c) => c.status != BlockadeContainerStatus::Up,
+                None => true,
+            })
+            .cloned()
+            .collect());
+    }
+
+    /// Picks a container uniformly at random from the cached state.  Returns
+    /// `BlockadeError::EmptyBlockade` if the blockade has no containers to choose from.
+    pub fn choose_random_container(&mut self, name: &str) -> Result<String, BlockadeError> {
+        return self.choose_random_container_except(name, &[]);
+    }
+
+    /// Like `choose_random_container`, but never picks any of the `exclude`d containers.
+    /// Returns `BlockadeError::EmptyBlockade` if no candidates remain.
+    pub fn choose_random_container_except(
+        &mut self,
+        name: &str,
+        exclude: &[String],
+    ) -> Result<String, BlockadeError> {
+        let candidates: Vec<&String> = match self.state.get(name) {
+            Some(state) => state
+                .containers
+                .keys()
+                .filter(|c| !exclude.contains(c))
+                .collect(),
+            None => return Err("Blockade not found in map".into()),
+        };
+        let mut rng = thread_rng();
+        return match seq::sample_iter(&mut rng, candidates, 1) {
+            Ok(mut chosen) => Ok(chosen.pop().unwrap().clone()),
+            Err(_) => Err(BlockadeError::EmptyBlockade(name.into())),
+        };
+    }
+
+    /// Picks a container from the cached state with probability proportional to its weight
+    /// in `weights`, e.g. to favour busy nodes.  Containers missing from `weights` weigh 1.0;
+    /// a weight of 0.0 excludes a container.  Negative or non-finite weights are an error.
+    pub fn choose_weighted_container(
+        &mut self,
+        name: &str,
+        weights: &HashMap<String, f64>,
+    ) -> Result<String, BlockadeError> {
+        let mut candidates: Vec<(&String, f64)> = match self.state.get(name) {
+            Some(state) => state
+                .containers
+                .keys()
+                .map(|c| (c, weights.get(c).cloned().unwrap_or(1.0)))
+                .collect(),
+            None => return Err("Blockade not found in map".into()),
+        };
+        if let Some(&(c, w)) = candidates.iter().find(|&&(_, w)| !w.is_finite() || w < 0.0) {
+            return Err(BlockadeError::OtherError(format!("Invalid weight {} for {}", w, c)));
+        }
+        candidates.sort_by(|a, b| a.0.cmp(b.0));
+        candidates.retain(|&(_, w)| w > 0.0);
+        if candidates.is_empty() {
+            return Err(BlockadeError::EmptyBlockade(name.into()));
+        }
+        let total: f64 = candidates.iter().map(|&(_, w)| w).sum();
+        let mut point = thread_rng().gen::<f64>() * total;
+        for &(c, w) in candidates.iter() {
+            if point < w {
+                return Ok(c.clone());
+            }
+            point -= w;
+        }
+        // Only reachable through rounding; the last candidate owns the remainder.
+        return Ok(candidates[candidates.len() - 1].0.clone());
+    }
+
+    /// Start a blockade from a given name and config struct.  The config is checked with
+    /// `BlockadeConfig::validate` before anything is sent to the daemon.  With `restart` set,
+    /// an existing blockade of the same name is destroyed and re-created; without it, the
+    /// clash is returned as `BlockadeError::Conflict`.  Any other setup error is returned.
+    /// Returns the initial state of the new blockade.
+    pub fn start_blockade(
+        &mut self,
+        name: &str,
+        config: BlockadeConfig,
+        restart: bool,
+    ) -> Result<BlockadeState, BlockadeError> {
+        config.validate()?;
+        match self.execute_setup(name, config.clone()) {
+            Ok(_) => {}
+            Err(BlockadeError::Conflict(_)) if restart => {
+                self.destroy_blockade(name)?;
+                self.execute_setup(name, config)?;
+            }
+            Err(e) => return Err(e),
+        };
+        return self.execute_get_blockade(name);
+    }
+
+    /// Like `start_blockade` without `restart`, but with `config.network.driver` replaced by
+    /// `driver`.  The stored config records the driver actually used.
+    pub fn start_blockade_with_driver(
+        &mut self,
+        name: &str,
+        mut config: BlockadeConfig,
+        driver: NetDriver,
+    ) -> Result<BlockadeState, BlockadeError> {
+        config.network.driver = driver;
+        return self.start_blockade(name, config, false);
+    }
+
+    /// Makes sure a blockade with this config exists, creating it only if the daemon doesn't
+    /// know the name.  The daemon can't report the config it was started with, so an existing
+    /// blockade is checked against what it can report (its set of containers) and against the
+    /// config this handler stored when it started the blockade, if any.  A mismatch is an
+    /// error; the running blockade is never torn down.
+    pub fn ensure_blockade(
+        &mut self,
+        name: &str,
+        config: BlockadeConfig,
+    ) -> Result<BlockadeState, BlockadeError> {
+        config.validate()?;
+        self.execute_list_blockades()?;
+        if !self.blockades.iter().any(|b| b == name) {
+            return self.start_blockade(name, config, false);
+        }
+
+        let state = self.execute_get_blockade(name)?;
+        let mut running: Vec<&String> = state.containers.keys().collect();
+        let mut wanted: Vec<&String> = config.containers.keys().collect();
+        running.sort();
+        wanted.sort();
+        if running != wanted {
+            return Err(BlockadeError::OtherError(format!(
+                "Blockade {} already exists with containers {:?}, expected {:?}",
+                name, running, wanted
+            )));
+        }
+        match self.config.get(name) {
+            Some(stored) if *stored != config => {
+                return Err(BlockadeError::OtherError(format!(
+                    "Blockade {} already exists with a different config",
+                    name
+                )));
+            }
+            Some(_) => {}
+            None => {
+                self.config.insert(name.into(), config);
+            }
+        }
+        return Ok(state);
+    }
+
+    /// The config this handler started a blockade with, if any.  Only blockades started by
+    /// this handler (or a clone made afterwards) are known: the daemon can't report the
+    /// config a running blockade was created from.
+    pub fn clone_config(&self, from: &str) -> Option<BlockadeConfig> {
+        return self.config.get(from).cloned();
+    }
+
+    /// The config a running blockade was created from.  Blockade's API has no way to read a
+    /// config back, so this can only confirm with the daemon that the blockade still exists
+    /// (refreshing its state) and then answer from the configs this handler stored.  For a
+    /// blockade created elsewhere it fails with an `OtherError`; insert the known config
+    /// into `config` to manage such a blockade with `ensure_blockade` or `clone_config`.
+    pub fn fetch_config(&mut self, name: &str) -> Result<BlockadeConfig, BlockadeError> {
+        self.execute_get_blockade(name)?;
+        return match self.config.get(name) {
+            Some(config) => Ok(config.clone()),
+            None => Err(BlockadeError::OtherError(format!(
+                "The daemon doesn't expose the config of blockade {}",
+                name
+            ))),
+        };
+    }
+
+    /// Starts `new_name` with the same config as `from`, which must have been started by this
+    /// handler (see `clone_config`).  Returns the new blockade's initial state.
+    pub fn start_blockade_from(
+        &mut self,
+        new_name: &str,
+        from: &str,
+    ) -> Result<BlockadeState, BlockadeError> {
+        let config = match self.clone_config(from) {
+            Some(c) => c,
+            None => {
+                return Err(BlockadeError::OtherError(format!(
+                    "No stored config for blockade {}",
+                    from
+                )))
+            }
+        };
+        return self.start_blockade(new_name, config, false);
+    }
+
+    /// Start a blockade and return a guard that destroys it when dropped, including while
+    /// unwinding from a panic.  The guard owns a clone of this handler (sharing its HTTP
+    /// client), so this handler's cache may still list the blockade after the guard is gone.
+    pub fn start_blockade_scoped(
+        &mut self,
+        name: &str,
+        config: BlockadeConfig,
+    ) -> Result<BlockadeGuard, BlockadeError> {
+        self.start_blockade(name, config, false)?;
+        return Ok(BlockadeGuard {
+            handler: self.clone(),
+            name: name.into(),
+        });
+    }
+
+    /// Start a container by blockade name and container name.  Returns the container's
+    /// refreshed state.
+    pub fn start_container(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Start, vec![container.into()])?;
+        let state = self.execute_get_blockade(name)?;
+        return take_container(state, container);
+    }
+
+    /// Start a container and block until the daemon reports it `Up` or `timeout` elapses.
+    pub fn start_container_and_wait(
+        &mut self,
+        name: &str,
+        container: &str,
+        timeout: Duration,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        self.start_container(name, container)?;
+        return self.wait_for_status(name, container, BlockadeContainerStatus::Up, timeout);
+    }
+
+    /// Polls the daemon until the container reports `status`.  On timeout the error names
+    /// the last observed status.
+    pub fn wait_for_status(
+        &mut self,
+        name: &str,
+        container: &str,
+        status: BlockadeContainerStatus,
+        timeout: Duration,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let state = take_container(self.execute_get_blockade(name)?, container)?;
+            if state.status == status {
+                return Ok(state);
+            }
+            if Instant::now() >= deadline {
+                return Err(BlockadeError::OtherError(format!(
+                    "Timed out waiting for container {} to be {}; last status was {}",
+                    container,
+                    status.to_str(),
+                    state.status.to_str()
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Stop a container by blockade name and container name.  Returns the container's
+    /// refreshed state.
+    pub fn stop_container(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Stop, vec![container.into()])?;
+        let state = self.execute_get_blockade(name)?;
+        return take_container(state, container);
+    }
+
+    /// Restart a container by blockade name and container name.  Returns the container's
+    /// refreshed state.
+    pub fn restart_container(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Restart, vec![container.into()])?;
+        let state = self.execute_get_blockade(name)?;
+        return take_container(state, container);
+    }
+
+    /// Restart a random-ish container.  Returns the name of the restarted container.
+    pub fn restart_one(&mut self, name: &str) -> Result<String, BlockadeError> {
+        return self.restart_one_except(name, &[]);
+    }
+
+    /// Restart a random-ish container other than those in `exclude`.  Returns the name of
+    /// the restarted container.
+    pub fn restart_one_except(
+        &mut self,
+        name: &str,
+        exclude: &[String],
+    ) -> Result<String, BlockadeError> {
+        let container = self.choose_random_container_except(name, exclude)?;
+        self.restart_container(name, &container)?;
+        return Ok(container);
+    }
+
+    /// Kills a container by blockade name and container name.  Returns the container's
+    /// refreshed state.
+    pub fn kill_container(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Kill, vec![container.into()])?;
+        let state = self.execute_get_blockade(name)?;
+        return take_container(state, container);
+    }
+
+    /// Simulates a crash loop: kills and restarts a container `times` times, waiting `delay`
+    /// after each kill and between rounds.  Stops at the first error.  Returns the
+    /// container's state after the last start.
+    pub fn crash_loop(
+        &mut self,
+        name: &str,
+        container: &str,
+        times: usize,
+        delay: Duration,
+    ) -> Result<BlockadeContainerState, BlockadeError> {
+        if times == 0 {
+            let state = self.execute_get_blockade(name)?;
+            return take_container(state, container);
+        }
+        for round in 0..times {
+            if round > 0 {
+                thread::sleep(delay);
+            }
+            self.kill_container(name, container)?;
+            thread::sleep(delay);
+            self.start_container(name, container)?;
+        }
+        let state = self.execute_get_blockade(name)?;
+        return take_container(state, container);
+    }
+
+    /// Kill a random-ish container.  Returns the name of the killed container.
+    pub fn kill_one(&mut self, name: &str) -> Result<String, BlockadeError> {
+        return self.kill_one_except(name, &[]);
+    }
+
+    /// Kill a random-ish container other than those in `exclude`, e.g. to spare a known
+    /// leader.  Returns the name of the killed container.
+    pub fn kill_one_except(
+        &mut self,
+        name: &str,
+        exclude: &[String],
+    ) -> Result<String, BlockadeError> {
+        let container = self.choose_random_container_except(name, exclude)?;
+        self.kill_container(name, &container)?;
+        return Ok(container);
+    }
+
+    /// Kills one random container in each of several blockades at once, one worker thread
+    /// per blockade.  Returns the killed container of each blockade.  If any blockade fails,
+    /// the error lists the failures along with what was killed elsewhere.
+    pub fn kill_one_in_each(
+        &mut self,
+        names: &[&str],
+    ) -> Result<HashMap<String, String>, BlockadeError> {
+        let mut failures = Vec::new();
+        let mut workers = Vec::new();
+        for name in names.iter() {
+            let container = match self.choose_random_container(name) {
+                Ok(c) => c,
+                Err(e) => {
+                    failures.push(format!("{} ({})", name, e));
+                    continue;
+                }
+            };
+            let mut worker = self.detached();
+            let worker_name = name.to_string();
+            let handle = thread::spawn(move || -> Result<String, BlockadeError> {
+                let kill = vec![container.clone()];
+                worker.execute_command(&worker_name, BlockadeCommand::Kill, kill)?;
+                return Ok(container);
+            });
+            workers.push((name.to_string(), handle));
+        }
+
+        let mut killed = HashMap::new();
+        for (name, handle) in workers {
+            match handle.join() {
+                Ok(Ok(container)) => {
+                    killed.insert(name, container);
+                }
+                Ok(Err(e)) => failures.push(format!("{} ({})", name, e)),
+                Err(_) => failures.push(format!("{} (worker panicked)", name)),
+            }
+        }
+        for name in killed.keys() {
+            if let Err(e) = self.execute_get_blockade(name) {
+                warn!("Failed to refresh blockade {}: {}", name, e);
+            }
+        }
+        if failures.is_empty() {
+            return Ok(killed);
+        }
+        return Err(BlockadeError::OtherError(format!(
+            "Failed to kill in blockades: {}; killed {:?}",
+            failures.join(", "),
+            killed
+        )));
+    }
+
+    /// Start several containers in a single batched request.  Returns the names of the
+    /// started containers.
+    pub fn start_containers(
+        &mut self,
+        name: &str,
+        containers: &[String],
+    ) -> Result<Vec<String>, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Start, containers.to_vec())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers.to_vec());
+    }
+
+    /// Stop several containers in a single batched request.  Returns the names of the
+    /// stopped containers.
+    pub fn stop_containers(
+        &mut self,
+        name: &str,
+        containers: &[String],
+    ) -> Result<Vec<String>, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Stop, containers.to_vec())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers.to_vec());
+    }
+
+    /// Restart several containers in a single batched request.  Returns the names of the
+    /// restarted containers.
+    pub fn restart_containers(
+        &mut self,
+        name: &str,
+        containers: &[String],
+    ) -> Result<Vec<String>, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Restart, containers.to_vec())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers.to_vec());
+    }
+
+    /// Kill several containers in a single batched request.  Returns the names of the
+    /// killed containers.
+    pub fn kill_containers(
+        &mut self,
+        name: &str,
+        containers: &[String],
+    ) -> Result<Vec<String>, BlockadeError> {
+        self.execute_command(name, BlockadeCommand::Kill, containers.to_vec())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers.to_vec());
+    }
+
+    /// Stops every container in the blockade with a single batched request.  Returns the
+    /// names of the stopped containers.
+    pub fn stop_all(&mut self, name: &str) -> Result<Vec<String>, BlockadeError> {
+        let all_containers = self.get_all_containers(name)?;
+        return self.stop_containers(name, &all_containers);
+    }
+
+    /// Kills every container in the blockade with a single batched request.  Returns the
+    /// names of the killed containers.
+    pub fn kill_all(&mut self, name: &str) -> Result<Vec<String>, BlockadeError> {
+        let all_containers = self.get_all_containers(name)?;
+        return self.kill_containers(name, &all_containers);
+    }
+
+    /// Tags containers of a blockade, e.g. with their role, so they can be acted on together
+    /// with `kill_tagged` and friends.  Replaces any containers the tag had before.  Tags
+    /// live only in this handler and, like its config, survive destroying the
+    /// blockade until `prune_stale`.
+    pub fn tag_containers(&mut self, name: &str, tag: &str, containers: Vec<String>) {
+        self.tags
+            .entry(name.into())
+            .or_default()
+            .insert(tag.into(), containers);
+    }
+
+    /// The containers of a blockade carrying `tag`.
+    pub fn get_tagged(&self, name: &str, tag: &str) -> Result<Vec<String>, BlockadeError> {
+        return match self.tags.get(name).and_then(|tags| tags.get(tag)) {
+            Some(containers) => Ok(containers.clone()),
+            None => Err(BlockadeError::OtherError(format!(
+                "No containers tagged {:?} in {}",
+                tag, name
+            ))),
+        };
+    }
+
+    /// Starts every container carrying `tag` with a single batched request.  Returns their
+    /// names.
+    pub fn start_tagged(&mut self, name: &str, tag: &str) -> Result<Vec<String>, BlockadeError> {
+        return self.command_tagged(name, tag, BlockadeCommand::Start);
+    }
+
+    /// Stops every container carrying `tag`.  Returns their names.
+    pub fn stop_tagged(&mut self, name: &str, tag: &str) -> Result<Vec<String>, BlockadeError> {
+        return self.command_tagged(name, tag, BlockadeCommand::Stop);
+    }
+
+    /// Restarts every container carrying `tag`.  Returns their names.
+    pub fn restart_tagged(
+        &mut self,
+        name: &str,
+        tag: &str,
+    ) -> Result<Vec<String>, BlockadeError> {
+        return self.command_tagged(name, tag, BlockadeCommand::Restart);
+    }
+
+    /// Kills every container carrying `tag`.  Returns their names.
+    pub fn kill_tagged(&mut self, name: &str, tag: &str) -> Result<Vec<String>, BlockadeError> {
+        return self.command_tagged(name, tag, BlockadeCommand::Kill);
+    }
+
+    fn command_tagged(
+        &mut self,
+        name: &str,
+        tag: &str,
+        command: BlockadeCommand,
+    ) -> Result<Vec<String>, BlockadeError> {
+        let containers = self.get_tagged(name, tag)?;
+        self.execute_command(name, command, containers.clone())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers);
+    }
+
+    /// Fetches a blockade's state straight from the daemon without touching the cache, so
+    /// it only needs `&self`, e.g. for monitors sharing a handler across threads.
+    pub fn get_state_uncached(&self, name: &str) -> Result<BlockadeState, BlockadeError> {
+        let raw_text = self.execute_get_blockade_raw(name)?;
+        return parse_state(&raw_text);
+    }
+
+    /// Returns the daemon's state response for a blockade as untyped JSON, including any
+    /// fields this crate doesn't model.  Useful when parsing into `BlockadeState` fails.
+    /// The cached state is left untouched.  An empty response comes back as `null`.
+    pub fn get_blockade_raw(&mut self, name: &str) -> Result<serde_json::Value, BlockadeError> {
+        let raw_text = self.execute_get_blockade_raw(name)?;
+        if raw_text.trim().is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        return parse_response(&raw_text);
+    }
+
+    /// Fetches a container's output, optionally only the last `tail` lines, in the order it
+    /// was written.  The blockade daemon has no log endpoint, so this asks the Docker Engine
+    /// API listening on `docker_socket` (usually `/var/run/docker.sock`) for the logs of the
+    /// container's `container_id`; it needs read access to that socket on the host running
+    /// the blockade's containers.  Containers with a TTY don't keep stderr apart, so all of
+    /// their output is reported as stdout.  Lines are returned, rather than one `String`, so
+    /// stdout and stderr stay apart without losing how they interleave; join the `text`s
+    /// for a single blob.  Needs the `docker-logs` feature, which turns on `unix-socket`.
+    #[cfg(all(unix, feature = "docker-logs"))]
+    pub fn get_container_logs<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        container: &str,
+        tail: Option<usize>,
+        docker_socket: P,
+    ) -> Result<Vec<LogLine>, BlockadeError> {
+        let state = take_container(self.execute_get_blockade(name)?, container)?;
+        if state.container_id.is_empty() {
+            return Err(BlockadeError::OtherError(format!(
+                "Container {} has no container_id to fetch logs for",
+                container
+            )));
+        }
+        let tail = match tail {
+            Some(n) => n.to_string(),
+            None => String::from("all"),
+        };
+        let url = format!(
+            "http://localhost/containers/{}/logs?stdout=1&stderr=1&tail={}",
+            state.container_id, tail
+        );
+        let request = self.client.get(&url).build()?;
+        let (status, body) = unix::send_bytes(docker_socket.as_ref(), &request, None)?;
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(BlockadeError::NotFound(String::from_utf8_lossy(&body).into_owned()));
+        }
+        if !status.is_success() {
+            return Err(BlockadeError::ServerError(String::from_utf8_lossy(&body).into_owned()));
+        }
+        return Ok(demux_docker_logs(&body));
+    }
+
+    /// Makes partitions according to the given nested Vec<Vec<String>> of container names.
+    pub fn make_partitions(
+        &mut self,
+        name: &str,
+        partitions: Vec<Vec<String>>,
+    ) -> Result<(), BlockadeError> {
+        self.execute_partition(name, partitions)?;
+        self.execute_get_blockade(name)?;
+        return Ok(());
+    }
+
+    /// Like `make_partitions`, but refuses (before sending anything) unless every container
+    /// of the blockade is in exactly one group, so no node is left connected to both sides.
+    /// See `BlockadeState::check_strict_partitions` for the error.
+    pub fn make_strict_partitions(
+        &mut self,
+        name: &str,
+        partitions: Vec<Vec<String>>,
+    ) -> Result<(), BlockadeError> {
+        self.execute_get_blockade(name)?.check_strict_partitions(&partitions)?;
+        return self.make_partitions(name, partitions);
+    }
+
+    /// Returns the current partition grouping of the blockade's containers after a state
+    /// refresh.  See `BlockadeState::partitions` for the ordering guarantees.
+    pub fn get_partitions(&mut self, name: &str) -> Result<Vec<Vec<String>>, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        return Ok(state.partitions());
+    }
+
+    /// Polls `get_partitions` until the grouping matches `expected`, since the daemon applies
+    /// partitions asynchronously.  Order within and across groups doesn't matter.  On timeout
+    /// the error includes the last observed grouping.
+    pub fn wait_for_partition(
+        &mut self,
+        name: &str,
+        expected: Vec<Vec<String>>,
+        timeout: Duration,
+    ) -> Result<Vec<Vec<String>>, BlockadeError> {
+        let mut expected = expected;
+        for group in expected.iter_mut() {
+            group.sort();
+        }
+        expected.sort();
+        let deadline = Instant::now() + timeout;
+        loop {
+            let partitions = self.get_partitions(name)?;
+            if partitions == expected {
+                return Ok(partitions);
+            }
+            if Instant::now() >= deadline {
+                return Err(BlockadeError::OtherError(format!(
+                    "Timed out waiting for blockade {} to be partitioned as {:?}; last saw {:?}",
+                    name, expected, partitions
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Refreshes the blockade's state and reports whether its network is currently split.
+    pub fn is_partitioned(&mut self, name: &str) -> Result<bool, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        return Ok(state.is_partitioned());
+    }
+
+    /// Refreshes the blockade's state and tells whether `from` can reach `to`, i.e. whether
+    /// they share a partition.  Only partitions are considered, not whether the containers
+    /// are up or their network is degraded.
+    pub fn can_reach(&mut self, name: &str, from: &str, to: &str) -> Result<bool, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        let from = take_container(state.clone(), from)?;
+        let to = take_container(state, to)?;
+        return Ok(from.partition == to.partition);
+    }
+
+    /// Puts all containers back in one partition.  Network conditions such as `flaky` or
+    /// `slow` are left as they are; see `heal_fully`.  Healing a blockade that isn't
+    /// partitioned succeeds, so this is safe to call in teardown.
+    pub fn heal_partitions(&mut self, name: &str) -> Result<(), BlockadeError> {
+        self.execute_restore_network(name)?;
+        self.execute_get_blockade(name)?;
+        return Ok(());
+    }
+
+    /// Resets a blockade to healthy: removes all partitions and sets every container's
+    /// network back to `fast`.
+    pub fn heal_fully(&mut self, name: &str) -> Result<(), BlockadeError> {
+        self.execute_restore_network(name)?;
+        self.make_net_fast(name)?;
+        return Ok(());
+    }
+
+    /// Halts all traffic between the blockade's containers.  Blockade has no pause endpoint,
+    /// so this puts every container in a partition of its own; traffic to and from the host
+    /// is unaffected.  Any existing partitions are replaced.
+    pub fn pause_network(&mut self, name: &str) -> Result<(), BlockadeError> {
+        let isolated = self
+            .get_all_containers(name)?
+            .into_iter()
+            .map(|c| vec![c])
+            .collect();
+        return self.make_partitions(name, isolated);
+    }
+
+    /// Lets traffic flow again after `pause_network`.  Like `heal_partitions`, which it is,
+    /// this also removes partitions made before the pause.
+    pub fn resume_network(&mut self, name: &str) -> Result<(), BlockadeError> {
+        return self.heal_partitions(name);
+    }
+
+    /// Makes the network condition generally bad.  Introduces at least latency and dropped packets
+    /// potentially also causes reordering of some magnitude.  Returns the affected containers.
+    pub fn make_net_unreliable(&mut self, name: &str) -> Result<Vec<String>, BlockadeError> {
+        let all_containers = self.get_all_containers(name)?;
+        self.execute_net_command(name, BlockadeNetStatus::Flaky, all_containers.clone())?;
+        self.execute_get_blockade(name)?;
+        return Ok(all_containers);
+    }
+
+    /// Makes the network condition as good as can be given the host conditions.  Generally this
+    /// means near perfect since the containers are usually on the local machine and the OS is
+    /// reasonably good about pushing packets.  Returns the affected containers.
+    pub fn make_net_fast(&mut self, name: &str) -> Result<Vec<String>, BlockadeError> {
+        let all_containers = self.get_all_containers(name)?;
+        self.execute_net_command(name, BlockadeNetStatus::Fast, all_containers.clone())?;
+        self.execute_get_blockade(name)?;
+        return Ok(all_containers);
+    }
+
+    /// Makes a random `fraction` (0.0 to 1.0) of the blockade's containers flaky, rounding
+    /// up, to model partial network trouble.  Returns the names of the affected containers.
+    pub fn make_random_net_flaky(
+        &mut self,
+        name: &str,
+        fraction: f64,
+    ) -> Result<Vec<String>, BlockadeError> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(BlockadeError::OtherError(format!(
+                "Fraction {} is not between 0 and 1",
+                fraction
+            )));
+        }
+        let all_containers = self.get_all_containers(name)?;
+        let count = (fraction * all_containers.len() as f64).ceil() as usize;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let mut rng = thread_rng();
+        let mut chosen = match seq::sample_iter(&mut rng, all_containers, count) {
+            Ok(chosen) => chosen,
+            Err(all) => all,
+        };
+        chosen.sort();
+        self.execute_net_command(name, BlockadeNetStatus::Flaky, chosen.clone())?;
+        self.execute_get_blockade(name)?;
+        return Ok(chosen);
     }
 
-    pub fn choose_random_container(&mut self, name: &str) -> Result<String, BlockadeError> {
-        if self.state.contains_key(name) && self.state[name].containers.keys().len() >= 1 {
-            let mut rng = thread_rng();
-            let state = self.state.clone();
-            let keys = state.get(name).unwrap().containers.keys();
-            let container = seq::sample_iter(&mut rng, keys, 1)
-                .unwrap()
-                .pop()
-                .unwrap()
-                .clone();
-            return Ok(container.into());
-        } else if !self.state.contains_key(name) {
-            return Err(BlockadeError::OtherError(String::from(
-                "Blockade not found in map",
-            )));
-        } else {
-            return Err(BlockadeError::OtherError(String::from(
-                "No containers to choose from",
+    /// Applies a network condition to only the containers in one partition, e.g. to slow
+    /// one side of a split while the other stays fast.  Refreshes the state to resolve the
+    /// partition and returns the names of the affected containers.
+    pub fn set_partition_net(
+        &mut self,
+        name: &str,
+        partition_id: u32,
+        network_state: BlockadeNetStatus,
+    ) -> Result<Vec<String>, BlockadeError> {
+        let state = self.execute_get_blockade(name)?;
+        let mut containers: Vec<String> = state
+            .containers
+            .iter()
+            .filter(|&(_, c)| c.partition == partition_id)
+            .map(|(container, _)| container.clone())
+            .collect();
+        if containers.is_empty() {
+            return Err(BlockadeError::OtherError(format!(
+                "no containers in partition {} of {}",
+                partition_id, name
             )));
         }
+        containers.sort();
+        self.execute_net_command(name, network_state, containers.clone())?;
+        self.execute_get_blockade(name)?;
+        return Ok(containers);
     }
 
-    /// Start a blockade from a given name and config struct.
-    pub fn start_blockade(
+    /// Applies a whole fault scenario at once: partitions the blockade as `spec` describes,
+    /// then sets each group's network condition.  If any step fails, the partitions are
+    /// healed and every container's previous network condition is put back (best-effort;
+    /// rollback failures are only logged) before the error is returned.  Returns the
+    /// applied spec.
+    pub fn inject_network_fault(
         &mut self,
         name: &str,
-        config: BlockadeConfig,
-        restart: bool,
-    ) -> Result<(), BlockadeError> {
-        match self.execute_setup(name, config.clone()) {
-            Ok(_) => {}
-            Err(e) => {
-                if restart {
-                    match e {
-                        BlockadeError::ServerError(s) => {
-                            if s == String::from("Blockade name already exists") {
-                                self.destroy_blockade(name)?;
-                                self.execute_setup(name, config.clone())?;
-                                return Ok(());
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        };
+        spec: FaultSpec,
+    ) -> Result<FaultSpec, BlockadeError> {
+        let before = self.execute_get_blockade(name)?;
+        if let Err(e) = self.apply_fault(name, &spec) {
+            let _ = self.roll_back_fault(name, &before);
+            return Err(e);
+        }
         self.execute_get_blockade(name)?;
-        return Ok(());
+        return Ok(spec);
     }
 
-    pub fn start_container(&mut self, name: &str, container: &str) -> Result<(), BlockadeError> {
-        self.execute_command(name, BlockadeCommand::Start, vec![container.into()])?;
+    /// Applies `fault` (see `inject_network_fault`) for at least `duration`: runs `f` with
+    /// the fault in place, sleeps out whatever is left of `duration`, then heals all
+    /// partitions and restores every container's previous network condition.  The cleanup
+    /// also runs if `f` panics, after which the panic carries on.  Returns what `f` returned,
+    /// or the first error from applying or undoing the fault.
+    pub fn with_temporary_fault<F, T>(
+        &mut self,
+        name: &str,
+        fault: FaultSpec,
+        duration: Duration,
+        f: F,
+    ) -> Result<T, BlockadeError>
+    where
+        F: FnOnce(&mut BlockadeHandler) -> T,
+    {
+        let before = self.execute_get_blockade(name)?;
+        let started = Instant::now();
+        self.inject_network_fault(name, fault)?;
+        let outcome = panic::catch_unwind(panic::AssertUnwindSafe(|| f(self)));
+        if outcome.is_ok() {
+            let elapsed = started.elapsed();
+            if elapsed < duration {
+                thread::sleep(duration - elapsed);
+            }
+        }
+        let cleanup = self.roll_back_fault(name, &before);
+        let value = match outcome {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        };
+        cleanup?;
         self.execute_get_blockade(name)?;
-        return Ok(());
+        return Ok(value);
     }
 
-    /// Stop a container by blockade name and container name.
-    pub fn stop_container(&mut self, name: &str, container: &str) -> Result<(), BlockadeError> {
-        self.execute_command(name, BlockadeCommand::Stop, vec![container.into()])?;
-        self.execute_get_blockade(name)?;
-        return Ok(());
+    /// Refreshes the blockade's state and returns the network condition of one container,
+    /// e.g. to check that `make_net_unreliable` took effect.
+    pub fn get_container_net_status(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<BlockadeNetStatus, BlockadeError> {
+        let state = take_container(self.execute_get_blockade(name)?, container)?;
+        return Ok(state.network_state);
     }
 
-    /// Restart a container by blockade name and container name.
-    pub fn restart_container(&mut self, name: &str, container: &str) -> Result<(), BlockadeError> {
-        self.execute_command(name, BlockadeCommand::Restart, vec![container.into()])?;
-        self.execute_get_blockade(name)?;
-        return Ok(());
+    /// Refreshes the blockade's state and returns the host network interface (veth) of one
+    /// container, e.g. to match it against `tc` rules.  `None` when the daemon didn't report
+    /// one.
+    pub fn get_container_device(
+        &mut self,
+        name: &str,
+        container: &str,
+    ) -> Result<Option<String>, BlockadeError> {
+        let state = take_container(self.execute_get_blockade(name)?, container)?;
+        if state.device.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(state.device));
     }
 
-    /// Restart a random-ish container.  Returns the name of the restarted container.
-    pub fn restart_one(&mut self, name: &str) -> Result<String, BlockadeError> {
-        let container = self.choose_random_container(name)?;
-        self.restart_container(name, &container)?;
-        return Ok(container);
+    /// Starts the daemon's chaos mode on a blockade, which keeps injecting random failures in
+    /// the background until `stop_chaos` is called.  See `ChaosConfig` for the schedule.
+    pub fn start_chaos(&mut self, name: &str, config: ChaosConfig) -> Result<(), BlockadeError> {
+        return self.execute_chaos(reqwest::Method::POST, name, Some(&config));
     }
 
-    /// Kills a container by blockade name and container name.
-    pub fn kill_container(&mut self, name: &str, container: &str) -> Result<(), BlockadeError> {
-        self.execute_command(name, BlockadeCommand::Kill, vec![container.into()])?;
+    /// Stops chaos mode on a blockade.  The daemon undoes any event still in effect.
+    pub fn stop_chaos(&mut self, name: &str) -> Result<(), BlockadeError> {
+        self.execute_chaos(reqwest::Method::DELETE, name, None)?;
         self.execute_get_blockade(name)?;
         return Ok(());
     }
 
-    /// Kill a random-ish container.  Returns the name of the killed container.
-    pub fn kill_one(&mut self, name: &str) -> Result<String, BlockadeError> {
-        let container = self.choose_random_container(name)?;
-        self.kill_container(name, &container)?;
-        return Ok(container);
-    }
-
-    /// Makes partitions according to the given nested Vec<Vec<String>> of container names.
-    pub fn make_partitions(
+    /// Swaps in new flaky/slow parameters for a blockade this handler started.  The daemon's
+    /// `network_state` action takes no inline parameters and only reads the network config at
+    /// setup, so this re-creates the blockade from its stored config, restarting its containers.
+    pub fn reconfigure_network(
         &mut self,
         name: &str,
-        partitions: Vec<Vec<String>>,
+        net: BlockadeNetConfig,
     ) -> Result<(), BlockadeError> {
-        self.execute_partition(name, partitions)?;
-        self.execute_get_blockade(name)?;
+        let mut config = match self.config.get(name) {
+            Some(c) => c.clone(),
+            None => {
+                return Err(BlockadeError::OtherError(format!(
+                    "No stored config for blockade {}",
+                    name
+                )))
+            }
+        };
+        config.network = net;
+        self.start_blockade(name, config, true)?;
         return Ok(());
     }
 
-    /// Puts all containers in one partition and restores the network QoS.
-    pub fn heal_partitions(&mut self, name: &str) -> Result<(), BlockadeError> {
-        self.execute_restore_network(name)?;
-        self.execute_get_blockade(name)?;
-        return Ok(());
-    }
+    /// Swaps in new flaky/slow parameters while keeping the blockade's faults, e.g. to ramp
+    /// packet loss during a run.  The daemon can't change netem settings in place, so like
+    /// `reconfigure_network` this re-creates the blockade and restarts every container; it
+    /// then puts back each container's network state (so degraded containers pick up the new
+    /// parameters), the partitions, and kills the containers that were down.  Anything else
+    /// about the old containers, like their ids and uptime, is lost.  The stored config is
+    /// kept in sync and the state is refreshed.
+    pub fn update_network_config(
+        &mut self,
+        name: &str,
+        net: BlockadeNetConfig,
+    ) -> Result<(), BlockadeError> {
+        let previous = self.execute_get_blockade(name)?;
+        self.reconfigure_network(name, net)?;
 
-    /// Makes the network condition generally bad.  Introduces at least latency and dropped packets
-    /// potentially also causes reordering of some magnitude.
-    pub fn make_net_unreliable(&mut self, name: &str) -> Result<(), BlockadeError> {
-        let all_containers = self.get_all_containers(name)?;
-        self.execute_net_command(name, BlockadeNetStatus::Flaky, all_containers)?;
+        let mut by_status: Vec<(BlockadeNetStatus, Vec<String>)> = Vec::new();
+        for (container, state) in previous.containers.iter() {
+            match state.network_state {
+                BlockadeNetStatus::Fast | BlockadeNetStatus::Unknown => continue,
+                _ => {}
+            }
+            match by_status.iter_mut().find(|(s, _)| *s == state.network_state) {
+                Some((_, containers)) => containers.push(container.clone()),
+                None => by_status.push((state.network_state.clone(), vec![container.clone()])),
+            }
+        }
+        for (status, containers) in by_status {
+            self.execute_net_command(name, status, containers)?;
+        }
+        if previous.is_partitioned() {
+            self.execute_partition(name, previous.partitions())?;
+        }
+        let down: Vec<String> = previous
+            .containers
+            .iter()
+            .filter(|(_, state)| state.status == BlockadeContainerStatus::Down)
+            .map(|(container, _)| container.clone())
+            .collect();
+        if !down.is_empty() {
+            self.execute_command(name, BlockadeCommand::Kill, down)?;
+        }
         self.execute_get_blockade(name)?;
         return Ok(());
     }
 
-    /// Makes the network condition as good as can be given the host conditions.  Generally this
-    /// means near perfect since the containers are usually on the local machine and the OS is
-    /// reasonably good about pushing packets.
-    pub fn make_net_fast(&mut self, name: &str) -> Result<(), BlockadeError> {
+    /// Makes every container reorder packets: the stored config's `slow` spec gets netem's
+    /// `reorder` option (e.g. `"25% 50%"`, see `LatencySpec::reorder`) and all containers
+    /// are switched to the slow network state.  The daemon has no separate reorder state and
+    /// only reads the network config at setup, so this re-creates the blockade like
+    /// `reconfigure_network`.  Returns the affected containers.
+    pub fn make_net_reorder(
+        &mut self,
+        name: &str,
+        reorder: &str,
+    ) -> Result<Vec<String>, BlockadeError> {
+        let mut net = match self.config.get(name) {
+            Some(c) => c.network.clone(),
+            None => {
+                return Err(BlockadeError::OtherError(format!(
+                    "No stored config for blockade {}",
+                    name
+                )))
+            }
+        };
+        let mut latency = LatencySpec::from_blockade_str(&net.slow)?;
+        latency.reorder = Some(reorder.into());
+        net.slow = latency.to_blockade_str();
+        self.reconfigure_network(name, net)?;
+
         let all_containers = self.get_all_containers(name)?;
-        self.execute_net_command(name, BlockadeNetStatus::Fast, all_containers)?;
+        self.execute_net_command(name, BlockadeNetStatus::Slow, all_containers.clone())?;
         self.execute_get_blockade(name)?;
-        return Ok(());
+        return Ok(all_containers);
     }
 
     /// Shuts down the blockade and all of its containers.  Probably don't want to use this
-    /// blockade afterward, considering it's pretty final.
-    pub fn destroy_blockade(&mut self, name: &str) -> Result<(), BlockadeError> {
-        self.execute_get_blockade(name)?;
-        self.execute_delete_blockade(name)?;
-        return Ok(());
+    /// blockade afterward, considering it's pretty final.  A blockade that doesn't exist is
+    /// not an error, so this is safe to call unconditionally during teardown; returns whether
+    /// there was anything to destroy.
+    pub fn destroy_blockade(&mut self, name: &str) -> Result<bool, BlockadeError> {
+        let result = match self.execute_get_blockade(name) {
+            Ok(_) => self.execute_delete_blockade(name),
+            Err(e) => Err(e),
+        };
+        return match result {
+            Ok(()) => Ok(true),
+            Err(BlockadeError::NotFound(_)) => {
+                self.forget(name);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        };
+    }
+
+    /// Asks the daemon whether the blockade exists, refreshing its cached state if it does and
+    /// dropping its state, stored config and tags if not.
+    pub fn blockade_exists(&mut self, name: &str) -> Result<bool, BlockadeError> {
+        return match self.execute_get_blockade(name) {
+            Ok(_) => Ok(true),
+            Err(BlockadeError::NotFound(_)) => {
+                self.forget(name);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        };
+    }
+
+    /// Polls until the daemon no longer knows the blockade, so a same-named blockade can be
+    /// created right after teardown.
+    pub fn wait_for_destroyed(
+        &mut self,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<(), BlockadeError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if !self.blockade_exists(name)? {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(BlockadeError::OtherError(format!(
+                    "Timed out waiting for blockade {} to be destroyed",
+                    name
+                )));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
     }
 
-    pub fn fetch_state(&mut self) -> Result<(), BlockadeError> {
+    /// Destroys every blockade the daemon knows about, including ones left behind by other
+    /// handlers or crashed runs.  Returns the names removed.  A blockade that fails to delete
+    /// doesn't stop the rest; if any fail, the error names each failure and what was removed.
+    pub fn destroy_all(&mut self) -> Result<Vec<String>, BlockadeError> {
         self.execute_list_blockades()?;
+        let mut removed = Vec::new();
+        let mut failures = Vec::new();
+        for blockade in self.blockades.clone() {
+            match self.execute_delete_blockade(&blockade) {
+                Ok(()) => removed.push(blockade),
+                Err(e) => failures.push(format!("{} ({})", blockade, e)),
+            }
+        }
+        self.blockades.retain(|b| !removed.contains(b));
+        if failures.is_empty() {
+            return Ok(removed);
+        }
+        return Err(BlockadeError::OtherError(format!(
+            "Failed to destroy blockades: {}; destroyed {:?}",
+            failures.join(", "),
+            removed
+        )));
+    }
+
+    /// Refreshes the blockade list and the state of every blockade.  The blockades are
+    /// fetched concurrently, and any that haven't answered within `timeout` are given up on.
+    /// A failing or hung blockade doesn't stop the others from being fetched; all failures
+    /// are returned together.  A failure to list the blockades is reported under an empty
+    /// name.
+    pub fn fetch_state(&mut self, timeout: Duration) -> Result<(), Vec<(String, BlockadeError)>> {
+        if let Err(e) = self.execute_list_blockades() {
+            return Err(vec![(String::new(), e)]);
+        }
         let blockades = self.blockades.clone();
-        for blockade in blockades.iter() {
-            self.execute_get_blockade(&blockade)?;
+        let mut errors = Vec::new();
+        for (blockade, result) in self.execute_get_blockades(&blockades, timeout) {
+            if let Err(e) = result {
+                errors.push((blockade, e));
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        return Err(errors);
+    }
+
+    /// Refreshes the cached state of just the named blockades, leaving the rest of the cache
+    /// and the blockade list alone.  Every name is tried; failures are returned together.
+    pub fn fetch_state_for(&mut self, names: &[&str]) -> Result<(), Vec<(String, BlockadeError)>> {
+        let mut errors = Vec::new();
+        for name in names.iter() {
+            if let Err(e) = self.execute_get_blockade(name) {
+                errors.push((name.to_string(), e));
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        return Err(errors);
+    }
+
+    /// Drops the cached state, config and tags of blockades the daemon no longer lists, e.g.
+    /// ones another client destroyed.  Returns the pruned names, sorted.  Dry runs prune
+    /// nothing.
+    pub fn prune_stale(&mut self) -> Result<Vec<String>, BlockadeError> {
+        if self.dry_run {
+            return Ok(Vec::new());
+        }
+        self.execute_list_blockades()?;
+        let mut stale: Vec<String> = self
+            .state
+            .keys()
+            .chain(self.config.keys())
+            .chain(self.tags.keys())
+            .filter(|name| !self.blockades.contains(name))
+            .cloned()
+            .collect();
+        stale.sort();
+        stale.dedup();
+        for name in stale.iter() {
+            self.forget(name);
+        }
+        return Ok(stale);
+    }
+
+    /// Drops everything cached about a blockade the daemon doesn't know (any more).
+    fn forget(&mut self, name: &str) {
+        self.state.remove(name);
+        self.config.remove(name);
+        self.tags.remove(name);
+        self.blockades.retain(|b| b != name);
+    }
+
+    /// Watches a blockade's state by polling.  The daemon has no event stream, so the
+    /// returned iterator GETs the state every `interval` (updating the cache as usual) and
+    /// yields it whenever it differs from the last state yielded, starting with the first.
+    /// Failed polls are yielded as errors and polling carries on.  The iterator never ends
+    /// by itself; stop with e.g. `take_while` or by dropping it.
+    pub fn poll_state(&mut self, name: &str, interval: Duration) -> StatePoller<'_> {
+        return StatePoller {
+            handler: self,
+            name: name.into(),
+            interval,
+            last: None,
+            polled: false,
+        };
+    }
+
+    /// A copy of this handler's connection settings with an empty cache, for running
+    /// requests on another thread.
+    fn detached(&self) -> Self {
+        return BlockadeHandler {
+            client: self.client.clone(),
+            host: self.host.clone(),
+            base_path: self.base_path.clone(),
+            auth: self.auth.clone(),
+            headers: self.headers.clone(),
+            blockades: Vec::new(),
+            state: HashMap::new(),
+            config: HashMap::new(),
+            tags: HashMap::new(),
+            dry_run: self.dry_run,
+            list_key: self.list_key.clone(),
+            transport: self.transport.clone(),
+            observer: self.observer.clone(),
+            on_transition: self.on_transition.clone(),
+        };
+    }
+
+    fn apply_fault(&mut self, name: &str, spec: &FaultSpec) -> Result<(), BlockadeError> {
+        self.execute_partition(name, spec.partitions())?;
+        for group in spec.groups.iter() {
+            if let Some(ref network) = group.network {
+                self.execute_net_command(name, network.clone(), group.containers.clone())?;
+            }
         }
         return Ok(());
     }
 
-    fn execute_setup(&mut self, name: &str, config: BlockadeConfig) -> Result<(), BlockadeError> {
-        self.config.insert(name.into(), config.clone());
+    /// Heals the blockade and puts back each container's network condition from `before`.
+    /// Carries on past failures, logging them, and returns the first.
+    fn roll_back_fault(&mut self, name: &str, before: &BlockadeState) -> Result<(), BlockadeError> {
+        let mut first_error = None;
+        if let Err(e) = self.execute_restore_network(name) {
+            warn!("Failed to heal blockade {} after a fault: {}", name, e);
+            first_error = Some(e);
+        }
+        let mut by_network: Vec<(BlockadeNetStatus, Vec<String>)> = Vec::new();
+        for (container, state) in before.containers.iter() {
+            match by_network.iter_mut().find(|(n, _)| *n == state.network_state) {
+                Some(entry) => entry.1.push(container.clone()),
+                None => by_network.push((state.network_state.clone(), vec![container.clone()])),
+            }
+        }
+        for (network, containers) in by_network {
+            if network == BlockadeNetStatus::Unknown {
+                continue;
+            }
+            if let Err(e) = self.execute_net_command(name, network, containers) {
+                warn!("Failed to restore the network of {} after a fault: {}", name, e);
+                first_error = first_error.or(Some(e));
+            }
+        }
+        return match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        };
+    }
+
+    /// Builds a full URL for a path relative to the blockade API root.
+    fn url(&self, path: &str) -> String {
+        return format!("{}{}{}", self.host, self.base_path, path);
+    }
+
+    /// Starts a request against a path relative to the blockade API root, with any
+    /// configured headers and credentials applied.
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let builder = self
+            .client
+            .request(method, self.url(path).as_str())
+            .headers(self.headers.clone());
+        return match self.auth {
+            Some(AuthScheme::Bearer(ref token)) => builder.bearer_auth(token),
+            Some(AuthScheme::Basic { ref user, ref pass }) => builder.basic_auth(user, Some(pass)),
+            None => builder,
+        };
+    }
+
+    /// Sends a request to the daemon and returns the response status and body.  The
+    /// exchange is reported to the observer, if one is set.
+    fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<(reqwest::StatusCode, String), BlockadeError> {
+        if self.dry_run {
+            info!(
+                "Dry run: {} {} {}",
+                method,
+                self.url(path),
+                body.unwrap_or_default()
+            );
+            return Ok((reqwest::StatusCode::NO_CONTENT, String::new()));
+        }
+        let mut builder = self.request(method, path);
+        if body.is_some() {
+            builder = builder.header(reqwest::header::CONTENT_TYPE, "application/json");
+        }
+        let (status, text) = self.transmit(builder, body.as_ref())?;
+        if let Some(ref observer) = self.observer {
+            let request_body = match body {
+                Some(ref json) => json.as_str(),
+                None => "",
+            };
+            (observer.0)(&self.url(path), request_body, status.as_u16(), &text);
+        }
+        return Ok((status, text));
+    }
+
+    /// Hands a prepared request to the transport.
+    fn transmit(
+        &self,
+        builder: reqwest::RequestBuilder,
+        body: Option<&String>,
+    ) -> Result<(reqwest::StatusCode, String), BlockadeError> {
+        let request = builder.build()?;
+        let (status, text) = (self.transport.0).send(&request, body.map(|b| b.as_str()))?;
+        return match reqwest::StatusCode::from_u16(status) {
+            Ok(status) => Ok((status, text)),
+            Err(_e) => Err(BlockadeError::OtherError(format!("Invalid status {}", status))),
+        };
+    }
+
+    /// The state a dry run reports: the cached state if there is one, otherwise every
+    /// container in the stored config, up and healthy.
+    fn dry_run_state(&self, name: &str) -> BlockadeState {
+        if let Some(s) = self.state.get(name) {
+            return s.clone();
+        }
+        let mut state = BlockadeState::default();
+        if let Some(config) = self.config.get(name) {
+            for container in config.containers.keys() {
+                state.containers.insert(
+                    container.clone(),
+                    BlockadeContainerState {
+                        name: container.clone(),
+                        network_state: BlockadeNetStatus::Fast,
+                        status: BlockadeContainerStatus::Up,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        return state;
+    }
 
+    fn execute_setup(&mut self, name: &str, config: BlockadeConfig) -> Result<(), BlockadeError> {
+        operation_span!("setup", name);
         let json = serde_json::to_string_pretty(&config).expect("Failed to serialize config");
         trace!("Config: {}", json);
 
-        let mut res = self.client
-            .post(format!("{}/blockade/{}", self.host, name).as_str())
-            .json(&config)
-            .send()?;
+        let (status, text) = self.send(
+            reqwest::Method::POST,
+            &format!("/{}", name),
+            Some(serde_json::to_string(&config)?),
+        )?;
 
-        debug!("Posted to server with status: {}", res.status());
+        debug!("Posted to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
+            self.config.insert(name.into(), config);
             return Ok(());
+        } else if is_conflict(status, name, &text) {
+            return Err(BlockadeError::Conflict(text));
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(text));
         }
     }
 
@@ -271,22 +1912,24 @@ impl BlockadeHandler {
         command: BlockadeCommand,
         containers: Vec<String>,
     ) -> Result<(), BlockadeError> {
+        operation_span!(command.to_str(), name, containers = ?containers);
         let args = BlockadeCommandArgs {
             command,
             container_names: containers,
         };
 
-        let mut res = self.client
-            .post(format!("{}/blockade/{}/action", self.host, name).as_str())
-            .json(&args)
-            .send()?;
+        let (status, text) = self.send(
+            reqwest::Method::POST,
+            &format!("/{}/action", name),
+            Some(serde_json::to_string(&args)?),
+        )?;
 
-        debug!("Posted to server with status: {}", res.status());
+        debug!("Posted to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
             return Ok(());
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(text));
         }
     }
 
@@ -296,22 +1939,24 @@ impl BlockadeHandler {
         network_state: BlockadeNetStatus,
         container_names: Vec<String>,
     ) -> Result<(), BlockadeError> {
+        operation_span!(network_state.to_str(), name, containers = ?container_names);
         let args = BlockadeNetArgs {
             network_state,
-            container_names: container_names,
+            container_names,
         };
 
-        let mut res = self.client
-            .post(format!("{}/blockade/{}/network_state", self.host, name).as_str())
-            .json(&args)
-            .send()?;
+        let (status, text) = self.send(
+            reqwest::Method::POST,
+            &format!("/{}/network_state", name),
+            Some(serde_json::to_string(&args)?),
+        )?;
 
-        debug!("Posted to server with status: {}", res.status());
+        debug!("Posted to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
             return Ok(());
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(text));
         }
     }
 
@@ -320,89 +1965,388 @@ impl BlockadeHandler {
         name: &str,
         partitions: Vec<Vec<String>>,
     ) -> Result<(), BlockadeError> {
+        operation_span!("partition", name, partitions = ?partitions);
         let args = BlockadePartitionArgs { partitions };
 
-        let mut res = self.client
-            .post(format!("{}/blockade/{}/partitions", self.host, name).as_str())
-            .json(&args)
-            .send()?;
+        let (status, text) = self.send(
+            reqwest::Method::POST,
+            &format!("/{}/partitions", name),
+            Some(serde_json::to_string(&args)?),
+        )?;
 
-        debug!("Posted to server with status: {}", res.status());
+        debug!("Posted to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
             return Ok(());
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(text));
         }
     }
 
     fn execute_restore_network(&mut self, name: &str) -> Result<(), BlockadeError> {
-        let mut res = self.client
-            .delete(format!("{}/blockade/{}/partitions", self.host, name).as_str())
-            .send()?;
+        operation_span!("heal", name);
+        let (status, text) =
+            self.send(reqwest::Method::DELETE, &format!("/{}/partitions", name), None)?;
+
+        debug!("Sent delete to server with status: {}", status);
+
+        if status.is_success() || is_already_healed(status, &text) {
+            return Ok(());
+        } else {
+            return Err(BlockadeError::ServerError(text));
+        }
+    }
+
+    fn execute_chaos(
+        &mut self,
+        method: reqwest::Method,
+        name: &str,
+        config: Option<&ChaosConfig>,
+    ) -> Result<(), BlockadeError> {
+        operation_span!("chaos", name, method = %method);
+        let body = match config {
+            Some(c) => Some(serde_json::to_string(c)?),
+            None => None,
+        };
+        let (status, text) = self.send(method, &format!("/{}/chaos", name), body)?;
 
-        debug!("Sent delete to server with status: {}", res.status());
+        debug!("Sent chaos request to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
             return Ok(());
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(text));
         }
     }
 
     fn execute_list_blockades(&mut self) -> Result<(), BlockadeError> {
-        let mut res = self.client
-            .get(format!("{}/blockade", self.host).as_str())
-            .send()?;
+        operation_span!("list");
+        if self.dry_run {
+            return Ok(());
+        }
+        let (status, raw_text) = self.send(reqwest::Method::GET, "", None)?;
 
-        debug!("Sent get to server with status: {}", res.status());
+        debug!("Sent get to server with status: {}", status);
 
-        if res.status().is_success() {
-            let raw_text = res.text()?;
+        if status.is_success() {
             debug!("Raw response from server: {:#?}", &raw_text);
-            let v: HashMap<String, Vec<String>> = serde_json::from_str(&raw_text)?;
-            self.blockades = match v.get("blockades") {
-                Some(n) => (n.clone()).into(),
-                None => Vec::new(),
-            };
+            self.blockades = parse_blockade_list(&raw_text, &self.list_key)?;
             return Ok(());
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(raw_text));
+        }
+    }
+
+    fn execute_get_blockade(&mut self, name: &str) -> Result<BlockadeState, BlockadeError> {
+        if self.dry_run {
+            let s = self.dry_run_state(name);
+            self.state.insert(name.into(), s.clone());
+            return Ok(s);
+        }
+        let raw_text = self.execute_get_blockade_raw(name)?;
+        return self.store_state(name, &raw_text);
+    }
+
+    /// Fetches several blockades at once, one worker thread each, giving up on any that
+    /// haven't answered within `timeout`.  Workers that time out are left to finish in the
+    /// background.  Results come back in the order of `names`.
+    fn execute_get_blockades(
+        &mut self,
+        names: &[String],
+        timeout: Duration,
+    ) -> Vec<(String, Result<BlockadeState, BlockadeError>)> {
+        if self.dry_run {
+            return names
+                .iter()
+                .map(|name| (name.clone(), self.execute_get_blockade(name)))
+                .collect();
+        }
+        let (tx, rx) = mpsc::channel();
+        for name in names.iter() {
+            let worker = self.detached();
+            let worker_name = name.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let result = worker.execute_get_blockade_raw(&worker_name);
+                let _ = tx.send((worker_name, result));
+            });
+        }
+        drop(tx);
+
+        let deadline = Instant::now() + timeout;
+        let mut raw = HashMap::new();
+        while raw.len() < names.len() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match rx.recv_timeout(deadline - now) {
+                Ok((name, result)) => {
+                    raw.insert(name, result);
+                }
+                Err(_) => break,
+            }
+        }
+
+        let mut results = Vec::new();
+        for name in names.iter() {
+            let result = match raw.remove(name) {
+                Some(Ok(raw_text)) => self.store_state(name, &raw_text),
+                Some(Err(e)) => Err(e),
+                None => Err(BlockadeError::OtherError(format!(
+                    "Timed out after {:?} fetching blockade {}",
+                    timeout, name
+                ))),
+            };
+            results.push((name.clone(), result));
+        }
+        return results;
+    }
+
+    /// Parses and caches a state response, reporting transitions from the cached state.
+    fn store_state(&mut self, name: &str, raw_text: &str) -> Result<BlockadeState, BlockadeError> {
+        let s = parse_state(raw_text)?;
+        if let Some(previous) = self.state.get(name) {
+            for change in previous.diff(&s).changed {
+                debug!("Blockade {} container {} changed: {:?}", name, change.name, change);
+                if let Some(ref hook) = self.on_transition {
+                    (hook.0)(name, &change.name, &change);
+                }
+            }
         }
+        self.state.insert(name.into(), s.clone());
+        return Ok(s);
     }
 
-    fn execute_get_blockade(&mut self, name: &str) -> Result<(), BlockadeError> {
-        let mut res = self.client
-            .get(format!("{}/blockade/{}", self.host, name).as_str())
-            .send()?;
+    fn execute_get_blockade_raw(&self, name: &str) -> Result<String, BlockadeError> {
+        operation_span!("get", name);
+        if self.dry_run {
+            return Ok(serde_json::to_string(&self.dry_run_state(name))?);
+        }
+        let (status, raw_text) = self.send(reqwest::Method::GET, &format!("/{}", name), None)?;
 
-        debug!("Sent get to server with status: {}", res.status());
+        debug!("Sent get to server with status: {}", status);
 
-        if res.status().is_success() {
-            let raw_text = res.text()?;
+        if status.is_success() {
             debug!("Raw response from server: {:#?}", &raw_text);
-            let s: BlockadeState = serde_json::from_str(&raw_text)?;
-            self.state.insert(name.into(), s);
-            return Ok(());
+            return Ok(raw_text);
+        } else if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(BlockadeError::NotFound(raw_text));
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            return Err(BlockadeError::ServerError(raw_text));
         }
     }
 
     fn execute_delete_blockade(&mut self, name: &str) -> Result<(), BlockadeError> {
-        let mut res = self.client
-            .delete(format!("{}/blockade/{}", self.host, name).as_str())
-            .send()?;
+        operation_span!("destroy", name);
+        let (status, text) = self.send(reqwest::Method::DELETE, &format!("/{}", name), None)?;
 
-        debug!("Sent delete to server with status: {}", res.status());
+        debug!("Sent delete to server with status: {}", status);
 
-        if res.status().is_success() {
+        if status.is_success() {
             if self.state.contains_key(name) {
                 self.state.remove(name);
             }
             return Ok(());
+        } else if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(BlockadeError::NotFound(text));
+        } else {
+            return Err(BlockadeError::ServerError(text));
+        }
+    }
+}
+
+/// Destroys its blockade on drop.  Created by `BlockadeHandler::start_blockade_scoped`.
+#[derive(Debug)]
+pub struct BlockadeGuard {
+    handler: BlockadeHandler,
+    name: String,
+}
+
+impl BlockadeGuard {
+    /// The name of the guarded blockade.
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+}
+
+impl Drop for BlockadeGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.handler.destroy_blockade(&self.name) {
+            warn!("Failed to destroy blockade {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Yields a blockade's state each time it changes.  Created by
+/// `BlockadeHandler::poll_state`.
+#[derive(Debug)]
+pub struct StatePoller<'a> {
+    handler: &'a mut BlockadeHandler,
+    name: String,
+    interval: Duration,
+    last: Option<BlockadeState>,
+    polled: bool,
+}
+
+impl<'a> Iterator for StatePoller<'a> {
+    type Item = Result<BlockadeState, BlockadeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.polled {
+                thread::sleep(self.interval);
+            }
+            self.polled = true;
+            match self.handler.execute_get_blockade(&self.name) {
+                Ok(state) => {
+                    if self.last.as_ref() != Some(&state) {
+                        self.last = Some(state.clone());
+                        return Some(Ok(state));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// The shapes daemons use to answer `GET /blockade`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BlockadeList {
+    Names(Vec<String>),
+    Keyed(HashMap<String, serde_json::Value>),
+}
+
+/// Accepts either `{"<key>": [...]}` or a bare `[...]` of blockade names.  Other fields of
+/// the object are ignored, but an object without the key is a `BadResponse` rather than
+/// an empty list.  Only an empty body, as sent with a 204, means no blockades.
+pub(crate) fn parse_blockade_list(raw_text: &str, key: &str) -> Result<Vec<String>, BlockadeError> {
+    if raw_text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    return match parse_response(raw_text)? {
+        BlockadeList::Names(names) => Ok(names),
+        BlockadeList::Keyed(mut map) => match map.remove(key) {
+            Some(names) => serde_json::from_value(names).map_err(|source| {
+                BlockadeError::BadResponse {
+                    source,
+                    raw: raw_text.into(),
+                }
+            }),
+            None => Err(BlockadeError::BadResponse {
+                source: serde::de::Error::custom(format!("missing field `{}`", key)),
+                raw: raw_text.into(),
+            }),
+        },
+    };
+}
+
+/// Splits a Docker logs response into lines.  Without a TTY, Docker frames each write with
+/// an 8-byte header: the stream (1 for stdout, 2 for stderr), three zero bytes and the
+/// big-endian payload length.  Anything else is a TTY's raw output.
+#[cfg(all(unix, feature = "docker-logs"))]
+fn demux_docker_logs(body: &[u8]) -> Vec<LogLine> {
+    let mut frames = Vec::new();
+    let mut rest = body;
+    while rest.len() >= 8 && (rest[0] == 1 || rest[0] == 2) && rest[1..4] == [0, 0, 0] {
+        let len = ((rest[4] as usize) << 24)
+            | ((rest[5] as usize) << 16)
+            | ((rest[6] as usize) << 8)
+            | rest[7] as usize;
+        if rest.len() < 8 + len {
+            break;
+        }
+        let stream = if rest[0] == 1 {
+            LogStream::Stdout
         } else {
-            return Err(BlockadeError::ServerError(res.text()?));
+            LogStream::Stderr
+        };
+        frames.push((stream, &rest[8..8 + len]));
+        rest = &rest[8 + len..];
+    }
+    if !rest.is_empty() {
+        // Not (or no longer) framed, so a TTY's output.
+        frames.push((LogStream::Stdout, rest));
+    }
+
+    let mut lines = Vec::new();
+    for (stream, payload) in frames {
+        for text in String::from_utf8_lossy(payload).lines() {
+            lines.push(LogLine {
+                stream,
+                text: text.to_owned(),
+            });
         }
     }
+    return lines;
+}
+
+/// Parses a state response.  An empty body (e.g. a 204) means no containers.
+fn parse_state(raw_text: &str) -> Result<BlockadeState, BlockadeError> {
+    if raw_text.trim().is_empty() {
+        return Ok(BlockadeState::default());
+    }
+    return parse_response(raw_text);
+}
+
+/// Parses a daemon response, keeping the raw text in the error if it doesn't fit `T`.
+fn parse_response<T: DeserializeOwned>(raw_text: &str) -> Result<T, BlockadeError> {
+    return serde_json::from_str(raw_text).map_err(|source| BlockadeError::BadResponse {
+        source,
+        raw: raw_text.into(),
+    });
+}
+
+/// Daemons report a name clash differently: blockade itself answers 400 "Blockade name
+/// already exists", others 409, or 500 with a message naming the blockade.  Something else
+/// that "already exists", like a Docker network left behind, is no clash of names.
+fn is_conflict(status: reqwest::StatusCode, name: &str, text: &str) -> bool {
+    if status == reqwest::StatusCode::CONFLICT {
+        return true;
+    }
+    if status != reqwest::StatusCode::BAD_REQUEST
+        && status != reqwest::StatusCode::INTERNAL_SERVER_ERROR
+    {
+        return false;
+    }
+    let lower = text.to_lowercase();
+    if !lower.contains("already exists") {
+        return false;
+    }
+    return lower.contains("blockade name")
+        || text
+            .split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '.'))
+            .any(|word| word == name);
+}
+
+/// Some daemons refuse to restore a blockade that has no partitions with a 4xx instead of
+/// a no-op.  A 404 still means the blockade itself is missing.
+fn is_already_healed(status: reqwest::StatusCode, text: &str) -> bool {
+    let text = text.to_lowercase();
+    return status.is_client_error()
+        && status != reqwest::StatusCode::NOT_FOUND
+        && (text.contains("no partition") || text.contains("not partitioned"));
+}
+
+fn take_container(
+    mut state: BlockadeState,
+    container: &str,
+) -> Result<BlockadeContainerState, BlockadeError> {
+    return match state.containers.remove(container) {
+        Some(c) => Ok(c),
+        None => Err(BlockadeError::OtherError(format!(
+            "Container {} not found in blockade state",
+            container
+        ))),
+    };
+}
+
+fn normalize_base_path(base_path: &str) -> String {
+    let trimmed = base_path.trim_matches('/');
+    if trimmed.is_empty() {
+        return String::new();
+    }
+    return format!("/{}", trimmed);
 }
@@ -1,15 +1,36 @@
-use std::collections::HashMap;
-use std::net::Ipv4Addr;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr};
 use std::fmt;
+use std::str;
+use std::time::Duration;
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::{self, Visitor};
+use serde_json;
 
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+use blockade::BlockadeError;
+
+/// The wire names of the crate's enums.  `from_str` panics on unknown input; prefer
+/// `str::parse` (every implementor is also `std::str::FromStr`), which returns an error.
 pub trait Stringify {
     fn to_str(&self) -> &str;
     fn from_str(val: &str) -> Self;
 }
 
+fn parse_or_panic<T: str::FromStr<Err = BlockadeError>>(val: &str) -> T {
+    return match val.parse() {
+        Ok(v) => v,
+        Err(e) => panic!("{}", e),
+    };
+}
+
+fn unexpected_input(val: &str) -> BlockadeError {
+    return BlockadeError::OtherError(format!("Unexpected enum input {:?}", val));
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum BlockadeCommand {
     Start,
@@ -29,12 +50,20 @@ impl Stringify for BlockadeCommand {
         };
     }
     fn from_str(val: &str) -> Self {
+        return parse_or_panic(val);
+    }
+}
+
+impl str::FromStr for BlockadeCommand {
+    type Err = BlockadeError;
+
+    fn from_str(val: &str) -> Result<Self, BlockadeError> {
         return match val {
-            "start" => BlockadeCommand::Start,
-            "stop" => BlockadeCommand::Stop,
-            "restart" => BlockadeCommand::Restart,
-            "kill" => BlockadeCommand::Kill,
-            x => panic!("Unexpected enum input {:?}", x),
+            "start" => Ok(BlockadeCommand::Start),
+            "stop" => Ok(BlockadeCommand::Stop),
+            "restart" => Ok(BlockadeCommand::Restart),
+            "kill" => Ok(BlockadeCommand::Kill),
+            x => Err(unexpected_input(x)),
         };
     }
 }
@@ -60,14 +89,24 @@ impl Stringify for BlockadeNetStatus {
         };
     }
     fn from_str(val: &str) -> Self {
-        return match val {
-            "NORMAL" => BlockadeNetStatus::Fast,
-            "FAST" => BlockadeNetStatus::Fast,
-            "SLOW" => BlockadeNetStatus::Slow,
-            "DUPLICATE" => BlockadeNetStatus::Duplicate,
-            "FLAKY" => BlockadeNetStatus::Flaky,
-            "UNKNOWN" => BlockadeNetStatus::Unknown,
-            x => panic!("Unexpected enum input {:?}", x),
+        return parse_or_panic(val);
+    }
+}
+
+impl str::FromStr for BlockadeNetStatus {
+    type Err = BlockadeError;
+
+    /// Accepts the daemon's upper-case names as well as the lower-case ones `to_str`
+    /// produces, in any case.
+    fn from_str(val: &str) -> Result<Self, BlockadeError> {
+        return match val.to_uppercase().as_str() {
+            "NORMAL" => Ok(BlockadeNetStatus::Fast),
+            "FAST" => Ok(BlockadeNetStatus::Fast),
+            "SLOW" => Ok(BlockadeNetStatus::Slow),
+            "DUPLICATE" => Ok(BlockadeNetStatus::Duplicate),
+            "FLAKY" => Ok(BlockadeNetStatus::Flaky),
+            "UNKNOWN" => Ok(BlockadeNetStatus::Unknown),
+            _ => Err(unexpected_input(val)),
         };
     }
 }
@@ -89,31 +128,155 @@ impl Stringify for BlockadeContainerStatus {
         };
     }
     fn from_str(val: &str) -> Self {
-        return match val {
-            "UP" => BlockadeContainerStatus::Up,
-            "DOWN" => BlockadeContainerStatus::Down,
-            "MISSING" => BlockadeContainerStatus::Missing,
-            x => panic!("Unexpected enum input {:?}", x),
+        return parse_or_panic(val);
+    }
+}
+
+impl str::FromStr for BlockadeContainerStatus {
+    type Err = BlockadeError;
+
+    /// Case-insensitive, like `BlockadeNetStatus`.
+    fn from_str(val: &str) -> Result<Self, BlockadeError> {
+        return match val.to_uppercase().as_str() {
+            "UP" => Ok(BlockadeContainerStatus::Up),
+            "DOWN" => Ok(BlockadeContainerStatus::Down),
+            "MISSING" => Ok(BlockadeContainerStatus::Missing),
+            _ => Err(unexpected_input(val)),
         };
     }
 }
 
+/// Network driver used by the blockade.  Unrecognized driver names are kept verbatim in
+/// `Other` so configs from newer daemons still deserialize, but `BlockadeConfig::validate`
+/// (and so `start_blockade`) refuses them, since a typo would otherwise only surface as a
+/// rejection from the daemon.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetDriver {
+    Udn,
+    Host,
+    Bridge,
+    Other(String),
+}
+
+impl Stringify for NetDriver {
+    fn to_str(&self) -> &str {
+        return match *self {
+            NetDriver::Udn => "udn",
+            NetDriver::Host => "host",
+            NetDriver::Bridge => "bridge",
+            NetDriver::Other(ref s) => s,
+        };
+    }
+    fn from_str(val: &str) -> Self {
+        return parse_or_panic(val);
+    }
+}
+
+impl str::FromStr for NetDriver {
+    type Err = BlockadeError;
+
+    /// Case-insensitive, like the other enums.  Never fails; unknown names become `Other`.
+    fn from_str(val: &str) -> Result<Self, BlockadeError> {
+        return Ok(match val.to_lowercase().as_str() {
+            "udn" => NetDriver::Udn,
+            "host" => NetDriver::Host,
+            "bridge" => NetDriver::Bridge,
+            _ => NetDriver::Other(val.into()),
+        });
+    }
+}
+
+/// A kind of failure the daemon's chaos mode may inject.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChaosEvent {
+    Partition,
+    Stop,
+    Flaky,
+    Slow,
+    Duplicate,
+}
+
+impl Stringify for ChaosEvent {
+    fn to_str(&self) -> &str {
+        return match *self {
+            ChaosEvent::Partition => "PARTITION",
+            ChaosEvent::Stop => "STOP",
+            ChaosEvent::Flaky => "FLAKY",
+            ChaosEvent::Slow => "SLOW",
+            ChaosEvent::Duplicate => "DUPLICATE",
+        };
+    }
+    fn from_str(val: &str) -> Self {
+        return parse_or_panic(val);
+    }
+}
+
+impl str::FromStr for ChaosEvent {
+    type Err = BlockadeError;
+
+    fn from_str(val: &str) -> Result<Self, BlockadeError> {
+        return match val.to_uppercase().as_str() {
+            "PARTITION" => Ok(ChaosEvent::Partition),
+            "STOP" => Ok(ChaosEvent::Stop),
+            "FLAKY" => Ok(ChaosEvent::Flaky),
+            "SLOW" => Ok(ChaosEvent::Slow),
+            "DUPLICATE" => Ok(ChaosEvent::Duplicate),
+            _ => Err(unexpected_input(val)),
+        };
+    }
+}
+
+/// A container command, either a single shell string or an argument vector.  Both forms
+/// are accepted by blockade and serialize as-is.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum CommandSpec {
+    Shell(String),
+    Args(Vec<String>),
+}
+
+impl<'a> From<&'a str> for CommandSpec {
+    fn from(command: &'a str) -> Self {
+        return CommandSpec::Shell(command.into());
+    }
+}
+
+impl From<String> for CommandSpec {
+    fn from(command: String) -> Self {
+        return CommandSpec::Shell(command);
+    }
+}
+
+impl From<Vec<String>> for CommandSpec {
+    fn from(args: Vec<String>) -> Self {
+        return CommandSpec::Args(args);
+    }
+}
+
+/// One container of a blockade config.  Field names are the daemon's config keys as-is.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct BlockadeContainer {
     pub image: String,
     pub hostname: String,
     pub volumes: HashMap<String, String>,
+    #[serde(deserialize_with = "port_list")]
     pub expose: Vec<u16>,
+    #[serde(deserialize_with = "port_map")]
     pub ports: HashMap<u16, u16>,
     pub links: HashMap<String, String>,
-    pub command: Option<String>,
+    pub command: Option<CommandSpec>,
+    #[serde(default)]
+    pub environment: HashMap<String, String>,
+    /// Any other container options (e.g. `cap_add`, `net`), passed to the daemon untouched.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct BlockadeNetConfig {
     pub flaky: String,
     pub slow: String,
-    pub driver: String,
+    pub driver: NetDriver,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -139,6 +302,130 @@ pub struct BlockadePartitionArgs {
     pub partitions: Vec<Vec<String>>,
 }
 
+/// Partitions keyed by a label, for readable topologies.  Labels only exist client-side:
+/// `to_args` turns them into the positional groups `make_partitions` sends.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NamedPartitions(pub HashMap<String, Vec<String>>);
+
+impl NamedPartitions {
+    /// The groups ordered by label, so the same partitions always produce the same request.
+    pub fn to_args(&self) -> Vec<Vec<String>> {
+        let mut labels: Vec<&String> = self.0.keys().collect();
+        labels.sort();
+        return labels.into_iter().map(|l| self.0[l].clone()).collect();
+    }
+}
+
+/// The field `BlockadeHandler::get_containers_sorted_by` orders containers by.  Ties are
+/// broken by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContainerSortKey {
+    Name,
+    /// IPv4 addresses before IPv6, each in numeric order.
+    IpAddress,
+    /// `Up`, then `Down`, then `Missing`.
+    Status,
+    Partition,
+}
+
+/// A network fault scenario for `BlockadeHandler::inject_network_fault`: a partitioning
+/// plus a network condition for the containers of each group.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FaultSpec {
+    pub groups: Vec<FaultGroup>,
+}
+
+/// One partition of a `FaultSpec`.  With `network` unset its containers' network is left
+/// as it is.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FaultGroup {
+    pub containers: Vec<String>,
+    pub network: Option<BlockadeNetStatus>,
+}
+
+impl FaultSpec {
+    /// The partition groups, in order, as `make_partitions` takes them.
+    pub fn partitions(&self) -> Vec<Vec<String>> {
+        return self.groups.iter().map(|g| g.containers.clone()).collect();
+    }
+}
+
+/// Reachability between containers: each container maps to those it can reach.  Blockade
+/// only supports partitions, where reachability is symmetric and transitive, so `to_args`
+/// rejects anything else instead of approximating it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Adjacency(pub HashMap<String, Vec<String>>);
+
+impl Adjacency {
+    /// The partition groups that give exactly this reachability, for `make_partitions`.
+    /// Every container named anywhere lands in a group; groups and their members are sorted.
+    /// As with any `make_partitions` call, the daemon puts the blockade's containers that
+    /// aren't named at all together in one more partition.
+    pub fn to_args(&self) -> Result<Vec<Vec<String>>, BlockadeError> {
+        let reaches = |from: &str, to: &str| -> bool {
+            return from == to || self.0.get(from).map_or(false, |r| r.iter().any(|c| c == to));
+        };
+        let mut containers: Vec<&String> = self.0.keys().chain(self.0.values().flatten()).collect();
+        containers.sort();
+        containers.dedup();
+
+        for (from, reachable) in self.0.iter() {
+            for to in reachable.iter() {
+                if !reaches(to, from) {
+                    return Err(BlockadeError::OtherError(format!(
+                        "{} reaches {} but not the reverse; blockade partitions are symmetric",
+                        from, to
+                    )));
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<String>> = Vec::new();
+        for container in containers {
+            let mut linked = groups
+                .iter_mut()
+                .filter(|g| g.iter().any(|c| reaches(c, container)));
+            let group = linked.next();
+            // Two containers that reach a common one must reach each other too.
+            let unlinked = match (&group, linked.next()) {
+                (Some(group), Some(other)) => Some((group[0].clone(), other[0].clone())),
+                (Some(group), None) => group
+                    .iter()
+                    .find(|c| !reaches(c, container))
+                    .map(|c| (c.clone(), container.clone())),
+                (None, _) => None,
+            };
+            if let Some((a, b)) = unlinked {
+                return Err(BlockadeError::OtherError(format!(
+                    "{} and {} are linked through other containers but don't reach each \
+                     other; blockade partitions are transitive",
+                    a, b
+                )));
+            }
+            match group {
+                Some(group) => group.push(container.clone()),
+                None => groups.push(vec![container.clone()]),
+            }
+        }
+        return Ok(groups);
+    }
+}
+
+/// Settings for the daemon's chaos mode, mirroring the body of `POST /blockade/<name>/chaos`.
+/// Delays and run times are in milliseconds.  Each round the daemon waits a random start
+/// delay, applies one event from `event_set` to a random number of containers, then undoes
+/// it after a random run time.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ChaosConfig {
+    pub min_start_delay: u64,
+    pub max_start_delay: u64,
+    pub min_run_time: u64,
+    pub max_run_time: u64,
+    pub min_containers_at_once: u32,
+    pub max_containers_at_once: u32,
+    pub event_set: Vec<ChaosEvent>,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct BlockadeState {
     pub containers: HashMap<String, BlockadeContainerState>,
@@ -152,10 +439,17 @@ fn none_u32_resource() -> u32 {
     return 0;
 }
 
-fn ip_default_resource() -> Ipv4Addr {
-    return Ipv4Addr::new(0, 0, 0, 0);
+fn ip_default_resource() -> IpAddr {
+    return IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
 }
 
+fn net_status_default_resource() -> BlockadeNetStatus {
+    return BlockadeNetStatus::Unknown;
+}
+
+/// Per-container state as reported by the daemon.  `container_id`, `name` and `status` are
+/// required and a response missing any of them fails with a `BadResponse` naming the field;
+/// everything else falls back to a default, and unrecognized fields are ignored.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct BlockadeContainerState {
     // present
@@ -165,16 +459,20 @@ pub struct BlockadeContainerState {
     pub device: String,
     // sometimes null, but is present
     #[serde(default = "ip_default_resource", deserialize_with = "nullable_ip")]
-    pub ip_address: Ipv4Addr,
+    pub ip_address: IpAddr,
     // present
     pub name: String,
-    // present
+    // present, but not critical; older daemons may omit it
+    #[serde(default = "net_status_default_resource")]
     pub network_state: BlockadeNetStatus,
     // present, sometimes null
     #[serde(default = "none_u32_resource", deserialize_with = "nullable_u32")]
     pub partition: u32,
     // present
     pub status: BlockadeContainerStatus,
+    // only reported by some daemons, as an RFC 3339 timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
 }
 
 fn nullable_u32<'de, D>(deserializer: D) -> Result<u32, D::Error>
@@ -184,11 +482,37 @@ where D: Deserializer<'de>
     Ok(opt.unwrap_or(0))
 }
 
-fn nullable_ip<'de, D>(deserializer: D) -> Result<Ipv4Addr, D::Error>
+fn nullable_ip<'de, D>(deserializer: D) -> Result<IpAddr, D::Error>
 where D: Deserializer<'de>
 {
     let opt = Option::deserialize(deserializer)?;
-    Ok(opt.unwrap_or(Ipv4Addr::new(0,0,0,0)))
+    Ok(opt.unwrap_or_else(ip_default_resource))
+}
+
+fn checked_port<E: de::Error>(port: i64) -> Result<u16, E> {
+    if port < 0 || port > i64::from(u16::MAX) {
+        return Err(E::custom(format!("port {} is out of range (0-65535)", port)));
+    }
+    return Ok(port as u16);
+}
+
+// Ports are read wide and range-checked so a bad value gets a descriptive error.
+fn port_list<'de, D>(deserializer: D) -> Result<Vec<u16>, D::Error>
+where D: Deserializer<'de>
+{
+    let ports: Vec<i64> = Vec::deserialize(deserializer)?;
+    return ports.into_iter().map(checked_port).collect();
+}
+
+fn port_map<'de, D>(deserializer: D) -> Result<HashMap<u16, u16>, D::Error>
+where D: Deserializer<'de>
+{
+    let ports: HashMap<i64, i64> = HashMap::deserialize(deserializer)?;
+    let mut checked = HashMap::new();
+    for (host, container) in ports {
+        checked.insert(checked_port(host)?, checked_port(container)?);
+    }
+    return Ok(checked);
 }
 
 impl Default for BlockadeContainer {
@@ -201,16 +525,25 @@ impl Default for BlockadeContainer {
             ports: HashMap::new(),
             links: HashMap::new(),
             command: None,
+            environment: HashMap::new(),
+            extra: HashMap::new(),
         };
     }
 }
 
+/// `(image, command)`, shorthand for `BlockadeContainer::simple(image, Some(command))`.
+impl<'a, 'b> From<(&'a str, &'b str)> for BlockadeContainer {
+    fn from((image, command): (&'a str, &'b str)) -> Self {
+        return BlockadeContainer::simple(image, Some(command));
+    }
+}
+
 impl Default for BlockadeNetConfig {
     fn default() -> Self {
         return BlockadeNetConfig {
             flaky: String::from("10%"),
             slow: String::from("75ms 100ms distribution normal"),
-            driver: String::from("udn"),
+            driver: NetDriver::Udn,
         };
     }
 }
@@ -242,6 +575,28 @@ impl Default for BlockadeNetArgs {
     }
 }
 
+/// The daemon's own defaults: every event type, one container at a time, 30 seconds to
+/// 5 minutes between and during events.
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        return ChaosConfig {
+            min_start_delay: 30000,
+            max_start_delay: 300000,
+            min_run_time: 30000,
+            max_run_time: 300000,
+            min_containers_at_once: 1,
+            max_containers_at_once: 1,
+            event_set: vec![
+                ChaosEvent::Partition,
+                ChaosEvent::Stop,
+                ChaosEvent::Flaky,
+                ChaosEvent::Slow,
+                ChaosEvent::Duplicate,
+            ],
+        };
+    }
+}
+
 impl Default for BlockadePartitionArgs {
     fn default() -> Self {
         return BlockadePartitionArgs {
@@ -258,18 +613,552 @@ impl Default for BlockadeState {
     }
 }
 
+#[cfg(feature = "chrono")]
+impl BlockadeContainerState {
+    /// When the container last started, if the daemon reported a valid RFC 3339 start time.
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        return self
+            .started_at
+            .as_ref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc));
+    }
+
+    /// Time since the container last started, if the daemon reported a start time.
+    pub fn uptime(&self) -> Option<chrono::Duration> {
+        return self.started_at().map(|t| Utc::now().signed_duration_since(t));
+    }
+}
+
 impl Default for BlockadeContainerState {
     fn default() -> Self {
         return BlockadeContainerState {
             container_id: String::new(),
             device: "".into(),
-            ip_address: Ipv4Addr::new(127, 0, 0, 2),
+            ip_address: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2)),
             name: String::new(),
             network_state: BlockadeNetStatus::Unknown,
             partition: 0,
             status: BlockadeContainerStatus::Missing,
+            started_at: None,
+        };
+    }
+}
+
+impl BlockadeContainer {
+    /// A container running `image` with an optional shell `command`; everything else is
+    /// as in `BlockadeContainer::default()`.
+    pub fn simple(image: &str, command: Option<&str>) -> Self {
+        return BlockadeContainer {
+            image: image.into(),
+            command: command.map(CommandSpec::from),
+            ..BlockadeContainer::default()
         };
     }
+
+    /// Exposes every port from `start` to `end` inclusive, skipping any already exposed.
+    pub fn expose_range(&mut self, start: u16, end: u16) -> &mut Self {
+        for port in start..=end {
+            if !self.expose.contains(&port) {
+                self.expose.push(port);
+            }
+        }
+        return self;
+    }
+
+    /// Whether a running container still looks like this config, stored under `name` (its
+    /// key in `BlockadeConfig::containers`), as far as the daemon can tell.  Its state carries
+    /// no image, hostname, ports or other settings, so the only thing compared is the name
+    /// the daemon gives the container, which is its config key.
+    pub fn matches_state(&self, name: &str, state: &BlockadeContainerState) -> bool {
+        return name == state.name;
+    }
+}
+
+impl BlockadeConfig {
+    /// A config with `n` identical containers named `c0`..`c{n-1}`, each a clone of
+    /// `template` (or `BlockadeContainer::default()`) with its hostname set to its name.
+    pub fn with_n_containers(n: usize, template: Option<BlockadeContainer>) -> Self {
+        let template = template.unwrap_or_default();
+        let mut config = BlockadeConfig::default();
+        for i in 0..n {
+            let name = format!("c{}", i);
+            let mut container = template.clone();
+            container.hostname = name.clone();
+            config.containers.insert(name, container);
+        }
+        return config;
+    }
+
+    /// Catches mistakes the daemon would reject: an unknown network driver, links to
+    /// containers that are not part of this config, zero or clashing ports, and volumes
+    /// without an absolute container path.
+    pub fn validate(&self) -> Result<(), BlockadeError> {
+        if let NetDriver::Other(ref driver) = self.network.driver {
+            return Err(BlockadeError::OtherError(format!(
+                "Unknown network driver {:?}; expected udn, host or bridge",
+                driver
+            )));
+        }
+        let mut names: Vec<&String> = self.containers.keys().collect();
+        names.sort();
+        let mut host_ports: HashMap<u16, &String> = HashMap::new();
+        for name in names {
+            let container = &self.containers[name];
+            for target in container.links.keys() {
+                if !self.containers.contains_key(target) {
+                    return Err(BlockadeError::OtherError(format!(
+                        "Container {} links to undefined container {}",
+                        name, target
+                    )));
+                }
+            }
+            if container.expose.contains(&0) {
+                return Err(BlockadeError::OtherError(format!(
+                    "Container {} exposes port 0",
+                    name
+                )));
+            }
+            for (host, inner) in container.ports.iter() {
+                if *host == 0 || *inner == 0 {
+                    return Err(BlockadeError::OtherError(format!(
+                        "Container {} maps port {} to {}; port 0 is not allowed",
+                        name, host, inner
+                    )));
+                }
+                if let Some(other) = host_ports.insert(*host, name) {
+                    return Err(BlockadeError::OtherError(format!(
+                        "Containers {} and {} both publish host port {}",
+                        other, name, host
+                    )));
+                }
+            }
+            for (host_path, container_path) in container.volumes.iter() {
+                if host_path.is_empty() || !container_path.starts_with('/') {
+                    return Err(BlockadeError::OtherError(format!(
+                        "Container {} has an invalid volume {:?} -> {:?}",
+                        name, host_path, container_path
+                    )));
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl BlockadeState {
+    /// Groups container names by their partition id, including the default partition 0.
+    /// Names are sorted within each group and the groups themselves are sorted, so the result
+    /// can be compared directly against an expected topology.
+    pub fn partitions(&self) -> Vec<Vec<String>> {
+        let mut groups: HashMap<u32, Vec<String>> = HashMap::new();
+        for (name, container) in self.containers.iter() {
+            groups
+                .entry(container.partition)
+                .or_default()
+                .push(name.clone());
+        }
+        let mut partitions: Vec<Vec<String>> = groups.into_values().collect();
+        for group in partitions.iter_mut() {
+            group.sort();
+        }
+        partitions.sort();
+        return partitions;
+    }
+
+    /// True only when more than one distinct non-zero partition exists.  A healed network
+    /// puts every container back in partition 0.
+    pub fn is_partitioned(&self) -> bool {
+        let mut ids: Vec<u32> = self
+            .containers
+            .values()
+            .map(|c| c.partition)
+            .filter(|p| *p != 0)
+            .collect();
+        ids.sort();
+        ids.dedup();
+        return ids.len() > 1;
+    }
+
+    /// Checks that `partitions` assigns every container to exactly one group and names no
+    /// unknown containers.  The error lists the missing, repeated and unknown names.
+    pub fn check_strict_partitions(&self, partitions: &[Vec<String>]) -> Result<(), BlockadeError> {
+        let mut seen: Vec<&String> = Vec::new();
+        let mut repeated = Vec::new();
+        let mut unknown = Vec::new();
+        for name in partitions.iter().flat_map(|group| group.iter()) {
+            if !self.containers.contains_key(name) {
+                unknown.push(name.clone());
+            } else if seen.contains(&name) {
+                repeated.push(name.clone());
+            } else {
+                seen.push(name);
+            }
+        }
+        let mut missing: Vec<String> = self
+            .containers
+            .keys()
+            .filter(|name| !seen.contains(name))
+            .cloned()
+            .collect();
+        if missing.is_empty() && repeated.is_empty() && unknown.is_empty() {
+            return Ok(());
+        }
+        missing.sort();
+        repeated.sort();
+        unknown.sort();
+        return Err(BlockadeError::OtherError(format!(
+            "Partitions must place every container exactly once; missing {:?}, repeated {:?}, \
+             unknown {:?}",
+            missing, repeated, unknown
+        )));
+    }
+
+    /// Container counts by status, plus the number of partition groups.
+    pub fn counts(&self) -> BlockadeCounts {
+        let mut counts = BlockadeCounts::default();
+        for container in self.containers.values() {
+            match container.status {
+                BlockadeContainerStatus::Up => counts.up += 1,
+                BlockadeContainerStatus::Down => counts.down += 1,
+                BlockadeContainerStatus::Missing => counts.missing += 1,
+            }
+        }
+        counts.partitions = self.partitions().len();
+        return counts;
+    }
+
+    /// Structural comparison against a later snapshot.  Only status, network state and
+    /// partition are compared; `container_id`, `ip_address` and `device` change across
+    /// restarts and are ignored.
+    pub fn diff(&self, other: &BlockadeState) -> StateDiff {
+        let mut diff = StateDiff::default();
+        for (name, before) in self.containers.iter() {
+            match other.containers.get(name) {
+                None => diff.removed.push(name.clone()),
+                Some(after) => {
+                    let change = ContainerChange {
+                        name: name.clone(),
+                        status: changed(&before.status, &after.status),
+                        network_state: changed(&before.network_state, &after.network_state),
+                        partition: changed(&before.partition, &after.partition),
+                    };
+                    if change.status.is_some()
+                        || change.network_state.is_some()
+                        || change.partition.is_some()
+                    {
+                        diff.changed.push(change);
+                    }
+                }
+            }
+        }
+        for name in other.containers.keys() {
+            if !self.containers.contains_key(name) {
+                diff.added.push(name.clone());
+            }
+        }
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort_by(|a, b| a.name.cmp(&b.name));
+        return diff;
+    }
+
+    /// A normalized copy for golden-file assertions: containers ordered by name, with the
+    /// fields `options` doesn't keep (by default everything that changes across runs)
+    /// left out.  Serializes deterministically.
+    pub fn to_snapshot(&self, options: &SnapshotOptions) -> StateSnapshot {
+        let containers = self
+            .containers
+            .iter()
+            .map(|(name, c)| {
+                let snapshot = ContainerSnapshot {
+                    status: c.status.clone(),
+                    network_state: c.network_state.clone(),
+                    partition: c.partition,
+                    container_id: keep(options.container_id, &c.container_id),
+                    ip_address: keep(options.ip_address, &c.ip_address),
+                    device: keep(options.device, &c.device),
+                };
+                (name.clone(), snapshot)
+            })
+            .collect();
+        return StateSnapshot { containers };
+    }
+
+    /// Whether this state's snapshot, taken with `options`, equals `expected`.
+    pub fn matches_snapshot(&self, expected: &StateSnapshot, options: &SnapshotOptions) -> bool {
+        return self.to_snapshot(options) == *expected;
+    }
+}
+
+fn changed<T: Clone + PartialEq>(before: &T, after: &T) -> Option<(T, T)> {
+    if before == after {
+        return None;
+    }
+    return Some((before.clone(), after.clone()));
+}
+
+fn keep<T: Clone>(wanted: bool, value: &T) -> Option<T> {
+    if wanted {
+        return Some(value.clone());
+    }
+    return None;
+}
+
+/// Which volatile fields `BlockadeState::to_snapshot` keeps.  All are dropped by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnapshotOptions {
+    pub container_id: bool,
+    pub ip_address: bool,
+    pub device: bool,
+}
+
+/// A normalized `BlockadeState`; see `BlockadeState::to_snapshot`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct StateSnapshot {
+    pub containers: BTreeMap<String, ContainerSnapshot>,
+}
+
+/// One container of a `StateSnapshot`.  Dropped fields are `None` and left out of the JSON.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ContainerSnapshot {
+    pub status: BlockadeContainerStatus,
+    pub network_state: BlockadeNetStatus,
+    pub partition: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+}
+
+/// The differences between two `BlockadeState` snapshots, with names sorted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ContainerChange>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        return self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty();
+    }
+}
+
+/// `(before, after)` transitions for a container present in both snapshots.  Fields that
+/// did not change are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerChange {
+    pub name: String,
+    pub status: Option<(BlockadeContainerStatus, BlockadeContainerStatus)>,
+    pub network_state: Option<(BlockadeNetStatus, BlockadeNetStatus)>,
+    pub partition: Option<(u32, u32)>,
+}
+
+/// Aggregate numbers over a handler's cached state.  See `BlockadeHandler::summary`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct BlockadeSummary {
+    pub blockades: HashMap<String, BlockadeCounts>,
+    /// Sum of the blockades' partition counts.
+    pub partitions: usize,
+}
+
+/// Container counts for one blockade.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct BlockadeCounts {
+    pub up: usize,
+    pub down: usize,
+    pub missing: usize,
+    /// Number of partition groups, as in `BlockadeState::partitions`; 1 when healed.
+    pub partitions: usize,
+}
+
+/// What is known about a daemon.  See `BlockadeHandler::get_daemon_info`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct DaemonInfo {
+    /// The `"version"` field of the `GET /blockade` response, which upstream blockade
+    /// doesn't send but some forks do.
+    pub version: Option<String>,
+    /// Whether the daemon has chaos mode; `None` when there was no blockade to probe it on.
+    pub chaos: Option<bool>,
+}
+
+/// Which of a container's output streams a log line was written to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// One line of a container's output.  See `BlockadeHandler::get_container_logs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub text: String,
+}
+
+impl fmt::Display for BlockadeContainerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(
+            f,
+            "{} {} partition={} ip={} net={}",
+            self.name,
+            self.status,
+            self.partition,
+            self.ip_address,
+            self.network_state
+        );
+    }
+}
+
+/// Renders one row per container, sorted by name, under a header line.
+impl fmt::Display for BlockadeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names: Vec<&String> = self.containers.keys().collect();
+        names.sort();
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+        write!(
+            f,
+            "{:<w$}  {:<7}  {:<9}  {:<15}  NET",
+            "NAME",
+            "STATUS",
+            "PARTITION",
+            "IP",
+            w = width
+        )?;
+        for name in names {
+            let c = &self.containers[name];
+            write!(
+                f,
+                "\n{:<w$}  {:<7}  {:<9}  {:<15}  {}",
+                name,
+                c.status,
+                c.partition,
+                c.ip_address.to_string(),
+                c.network_state,
+                w = width
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// A typed form of `BlockadeNetConfig::slow`, which the daemon hands to netem as
+/// `<delay> [<jitter>] [distribution <name>] [reorder <percent> [<correlation>]]`, e.g.
+/// `"75ms 100ms distribution normal"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencySpec {
+    pub delay: Duration,
+    pub jitter: Option<Duration>,
+    pub distribution: Option<String>,
+    /// netem's reorder arguments, e.g. `"25%"` or `"25% 50%"`: that share of packets skips
+    /// the delay and so overtakes the ones before it.
+    pub reorder: Option<String>,
+}
+
+impl LatencySpec {
+    /// Parses a `slow` string.  Times take a `us`, `ms` or `s` suffix.
+    pub fn from_blockade_str(val: &str) -> Result<LatencySpec, BlockadeError> {
+        let mut tokens = val.split_whitespace();
+        let delay = match tokens.next() {
+            Some(t) => parse_netem_time(t)?,
+            None => return Err(BlockadeError::OtherError("Empty latency spec".into())),
+        };
+        let mut spec = LatencySpec {
+            delay,
+            jitter: None,
+            distribution: None,
+            reorder: None,
+        };
+        let mut tokens = tokens.peekable();
+        while let Some(token) = tokens.next() {
+            if spec.reorder.is_some() {
+                return Err(BlockadeError::OtherError(format!(
+                    "Unexpected {:?} after reorder in latency spec {:?}",
+                    token, val
+                )));
+            } else if token == "reorder" {
+                let mut percents = Vec::new();
+                while percents.len() < 2 && tokens.peek().map_or(false, |t| t.ends_with('%')) {
+                    percents.push(tokens.next().unwrap());
+                }
+                if percents.is_empty() {
+                    return Err(BlockadeError::OtherError(format!(
+                        "Missing reorder percentage in latency spec {:?}",
+                        val
+                    )));
+                }
+                spec.reorder = Some(percents.join(" "));
+            } else if token == "distribution" {
+                match tokens.next() {
+                    Some(name) => spec.distribution = Some(name.into()),
+                    None => {
+                        return Err(BlockadeError::OtherError(format!(
+                            "Missing distribution name in latency spec {:?}",
+                            val
+                        )))
+                    }
+                }
+            } else if spec.jitter.is_none() && spec.distribution.is_none() {
+                spec.jitter = Some(parse_netem_time(token)?);
+            } else {
+                return Err(BlockadeError::OtherError(format!(
+                    "Unexpected {:?} in latency spec {:?}",
+                    token, val
+                )));
+            }
+        }
+        return Ok(spec);
+    }
+
+    /// The inverse of `from_blockade_str`, suitable for `BlockadeNetConfig::slow`.
+    pub fn to_blockade_str(&self) -> String {
+        let mut out = format_netem_time(self.delay);
+        if let Some(jitter) = self.jitter {
+            out.push(' ');
+            out.push_str(&format_netem_time(jitter));
+        }
+        if let Some(ref name) = self.distribution {
+            out.push_str(" distribution ");
+            out.push_str(name);
+        }
+        if let Some(ref reorder) = self.reorder {
+            out.push_str(" reorder ");
+            out.push_str(reorder);
+        }
+        return out;
+    }
+}
+
+fn parse_netem_time(token: &str) -> Result<Duration, BlockadeError> {
+    let (number, unit_nanos) = if let Some(n) = token.strip_suffix("us") {
+        (n, 1_000)
+    } else if let Some(n) = token.strip_suffix("ms") {
+        (n, 1_000_000)
+    } else if let Some(n) = token.strip_suffix('s') {
+        (n, 1_000_000_000)
+    } else {
+        return Err(BlockadeError::OtherError(format!(
+            "Time {:?} needs a unit (us, ms or s)",
+            token
+        )));
+    };
+    return match number.parse::<f64>() {
+        Ok(n) if n >= 0.0 => Ok(Duration::from_nanos((n * unit_nanos as f64).round() as u64)),
+        _ => Err(BlockadeError::OtherError(format!("Invalid time {:?}", token))),
+    };
+}
+
+fn format_netem_time(time: Duration) -> String {
+    let micros = time.as_micros();
+    if micros % 1_000_000 == 0 && micros > 0 {
+        return format!("{}s", micros / 1_000_000);
+    } else if micros % 1_000 == 0 {
+        return format!("{}ms", micros / 1_000);
+    }
+    return format!("{}us", micros);
 }
 
 macro_rules! serialize_impl {
@@ -288,6 +1177,25 @@ macro_rules! serialize_impl {
 serialize_impl!(BlockadeCommand);
 serialize_impl!(BlockadeNetStatus);
 serialize_impl!(BlockadeContainerStatus);
+serialize_impl!(NetDriver);
+serialize_impl!(ChaosEvent);
+
+/// Formats with the `Stringify` wire name, honouring width and alignment.
+macro_rules! display_impl {
+    ($($t:ty)*) => ($(
+        impl fmt::Display for $t {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.pad(self.to_str())
+            }
+        }
+    )*)
+}
+
+display_impl!(BlockadeCommand);
+display_impl!(BlockadeNetStatus);
+display_impl!(BlockadeContainerStatus);
+display_impl!(NetDriver);
+display_impl!(ChaosEvent);
 
 macro_rules! deserialize_impl {
     ($($t:ty)*, $s:ident) => ($(
@@ -305,7 +1213,7 @@ macro_rules! deserialize_impl {
             where
                 E: de::Error,
             {
-                Ok(Stringify::from_str(value))
+                value.parse().map_err(E::custom)
             }
         }
 
@@ -323,3 +1231,5 @@ macro_rules! deserialize_impl {
 deserialize_impl!(BlockadeCommand, BlockadeCommandVisitor);
 deserialize_impl!(BlockadeNetStatus, BlockadeNetStatusVisitor);
 deserialize_impl!(BlockadeContainerStatus, BlockadeContainerStatusVisitor);
+deserialize_impl!(NetDriver, NetDriverVisitor);
+deserialize_impl!(ChaosEvent, ChaosEventVisitor);
@@ -1,16 +1,26 @@
+#![allow(clippy::needless_return)]
+
 #[macro_use]
 extern crate log;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 extern crate rand;
 extern crate reqwest;
 extern crate serde;
-#[macro_use]
-extern crate serde_derive;
 extern crate serde_json;
 
 mod blockade;
 mod common;
+#[cfg(all(unix, feature = "unix-socket"))]
+mod unix;
+#[cfg(any(test, feature = "test-server"))]
+mod mock;
 pub use blockade::BlockadeError as Error;
 pub use blockade::*;
 pub use common::*;
+#[cfg(any(test, feature = "test-server"))]
+pub use mock::{MockBlockadeServer, MockRequest};
 #[cfg(test)]
 mod tests;
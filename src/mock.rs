@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde_json::{self, Value};
+
+use common::*;
+
+/// A request received by the mock server, kept for later assertions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    names: Vec<String>,
+    blockades: HashMap<String, BlockadeState>,
+    requests: Vec<MockRequest>,
+    failures: Vec<(u16, String)>,
+    chaos: HashMap<String, ChaosConfig>,
+    delay: Duration,
+}
+
+/// An in-memory stand-in for the blockade daemon's REST API, listening on a local port.
+/// Only the `/blockade` endpoints used by `BlockadeHandler` are implemented; containers are
+/// never actually run, their reported state is just updated in place.
+#[derive(Debug)]
+pub struct MockBlockadeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockBlockadeServer {
+    /// Binds to an ephemeral port on localhost and starts serving in a background thread.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_state = state.clone();
+        let thread_shutdown = shutdown.clone();
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    if let Err(e) = serve_connection(stream, &thread_state) {
+                        debug!("Mock server connection error: {}", e);
+                    }
+                }
+            }
+        });
+
+        return Ok(MockBlockadeServer {
+            addr,
+            state,
+            shutdown,
+            handle: Some(handle),
+        });
+    }
+
+    /// The base URL to hand to `BlockadeHandler::new`.
+    pub fn url(&self) -> String {
+        return format!("http://{}", self.addr);
+    }
+
+    /// The server-side state of a blockade, if it exists.
+    pub fn blockade_state(&self, name: &str) -> Option<BlockadeState> {
+        return self.state.lock().unwrap().blockades.get(name).cloned();
+    }
+
+    /// The chaos settings running on a blockade, if chaos mode is on.
+    pub fn chaos_config(&self, name: &str) -> Option<ChaosConfig> {
+        return self.state.lock().unwrap().chaos.get(name).cloned();
+    }
+
+    /// Every request received so far, in arrival order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        return self.state.lock().unwrap().requests.clone();
+    }
+
+    /// Makes the next request fail with the given status and body instead of being handled.
+    /// Calls queue up, so several failures can be injected in a row.
+    pub fn fail_next(&self, status: u16, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .push((status, body.into()));
+    }
+
+    /// Holds back every later response by `delay`, as a daemon busy rewriting iptables would.
+    pub fn delay_responses(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+}
+
+impl Drop for MockBlockadeServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it can observe the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve_connection(stream: TcpStream, state: &Arc<Mutex<MockState>>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_owned();
+    let path = parts.next().unwrap_or("").to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(idx) = line.find(':') {
+            headers.insert(
+                line[..idx].trim().to_lowercase(),
+                line[idx + 1..].trim().to_owned(),
+            );
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body).into_owned();
+
+    let request = MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    };
+    let (status, response, delay) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        let (status, response) = if state.failures.is_empty() {
+            handle_request(&mut state, &request)
+        } else {
+            state.failures.remove(0)
+        };
+        (status, response, state.delay)
+    };
+    thread::sleep(delay);
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        response.len(),
+        response
+    )?;
+    stream.flush()?;
+    return Ok(());
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    return match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    };
+}
+
+fn handle_request(state: &mut MockState, request: &MockRequest) -> (u16, String) {
+    let path = request.path.trim_end_matches('/');
+    let segments: Vec<&str> = match path.find("/blockade") {
+        Some(0) => path["/blockade".len()..]
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect(),
+        _ => return (404, "Not found".into()),
+    };
+
+    return match (request.method.as_str(), segments.as_slice()) {
+        ("GET", []) => {
+            let body = serde_json::json!({ "blockades": state.names });
+            (200, body.to_string())
+        }
+        ("POST", [name]) => create_blockade(state, name, &request.body),
+        ("GET", [name]) => match state.blockades.get(*name) {
+            Some(s) => (200, state_to_wire(s).to_string()),
+            None => (404, "Blockade not found".into()),
+        },
+        ("DELETE", [name]) => match state.blockades.remove(*name) {
+            Some(_) => {
+                state.names.retain(|n| n != name);
+                (204, String::new())
+            }
+            None => (404, "Blockade not found".into()),
+        },
+        ("POST", [name, "action"]) => with_blockade(state, name, &request.body, apply_action),
+        ("POST", [name, "network_state"]) => {
+            with_blockade(state, name, &request.body, apply_network_state)
+        }
+        ("POST", [name, "partitions"]) => {
+            with_blockade(state, name, &request.body, apply_partitions)
+        }
+        ("DELETE", [name, "partitions"]) => match state.blockades.get_mut(*name) {
+            Some(s) => {
+                for container in s.containers.values_mut() {
+                    container.partition = 0;
+                }
+                (204, String::new())
+            }
+            None => (404, "Blockade not found".into()),
+        },
+        ("POST", [name, "chaos"]) => start_chaos(state, name, &request.body),
+        ("DELETE", [name, "chaos"]) => match state.chaos.remove(*name) {
+            Some(_) => (204, String::new()),
+            None => (400, "Chaos is not running".into()),
+        },
+        ("GET", [name, "chaos"]) => match state.chaos.get(*name) {
+            Some(config) => (200, serde_json::to_string(config).unwrap()),
+            None if state.blockades.contains_key(*name) => (400, "Chaos is not running".into()),
+            None => (404, "Blockade not found".into()),
+        },
+        _ => (404, "Not found".into()),
+    };
+}
+
+fn start_chaos(state: &mut MockState, name: &str, body: &str) -> (u16, String) {
+    if !state.blockades.contains_key(name) {
+        return (404, "Blockade not found".into());
+    }
+    if state.chaos.contains_key(name) {
+        return (400, "Chaos is already running".into());
+    }
+    return match serde_json::from_str(body) {
+        Ok(config) => {
+            state.chaos.insert(name.into(), config);
+            (201, format!("Successfully started chaos on {}", name))
+        }
+        Err(e) => (400, format!("Invalid chaos config: {}", e)),
+    };
+}
+
+fn create_blockade(state: &mut MockState, name: &str, body: &str) -> (u16, String) {
+    if state.blockades.contains_key(name) {
+        return (400, "Blockade name already exists".into());
+    }
+    let config: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => return (400, format!("Invalid config: {}", e)),
+    };
+    let mut container_names: Vec<String> = match config["containers"].as_object() {
+        Some(c) => c.keys().cloned().collect(),
+        None => return (400, "Config has no containers".into()),
+    };
+    container_names.sort();
+
+    // Addresses count up from 172.17.0.2, like Docker's default bridge network hands out.
+    let first_ip = u32::from(Ipv4Addr::new(172, 17, 0, 2));
+    let mut blockade = BlockadeState::default();
+    for (i, container) in container_names.iter().enumerate() {
+        blockade.containers.insert(
+            container.clone(),
+            BlockadeContainerState {
+                container_id: format!("mock-{}-{}", name, container),
+                device: format!("veth{}", i),
+                ip_address: IpAddr::V4(Ipv4Addr::from(first_ip + i as u32)),
+                name: container.clone(),
+                network_state: BlockadeNetStatus::Fast,
+                partition: 0,
+                status: BlockadeContainerStatus::Up,
+                started_at: None,
+            },
+        );
+    }
+    state.names.push(name.into());
+    state.blockades.insert(name.into(), blockade);
+    return (204, String::new());
+}
+
+fn with_blockade<F>(state: &mut MockState, name: &str, body: &str, f: F) -> (u16, String)
+where
+    F: Fn(&mut BlockadeState, &Value) -> Result<(), String>,
+{
+    let args: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => return (400, format!("Invalid request body: {}", e)),
+    };
+    return match state.blockades.get_mut(name) {
+        Some(s) => match f(s, &args) {
+            Ok(()) => (204, String::new()),
+            Err(e) => (400, e),
+        },
+        None => (404, "Blockade not found".into()),
+    };
+}
+
+fn string_list(value: &Value) -> Vec<String> {
+    return match value.as_array() {
+        Some(items) => items
+            .iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        None => Vec::new(),
+    };
+}
+
+fn apply_action(state: &mut BlockadeState, args: &Value) -> Result<(), String> {
+    let status = match args["command"].as_str() {
+        Some("start") | Some("restart") => BlockadeContainerStatus::Up,
+        Some("stop") | Some("kill") => BlockadeContainerStatus::Down,
+        other => return Err(format!("Unknown command {:?}", other)),
+    };
+    for name in string_list(&args["container_names"]) {
+        match state.containers.get_mut(&name) {
+            Some(c) => c.status = status.clone(),
+            None => return Err(format!("Unknown container {}", name)),
+        }
+    }
+    return Ok(());
+}
+
+fn apply_network_state(state: &mut BlockadeState, args: &Value) -> Result<(), String> {
+    let network_state = match args["network_state"].as_str() {
+        Some("fast") => BlockadeNetStatus::Fast,
+        Some("slow") => BlockadeNetStatus::Slow,
+        Some("duplicate") => BlockadeNetStatus::Duplicate,
+        Some("flaky") => BlockadeNetStatus::Flaky,
+        other => return Err(format!("Unknown network state {:?}", other)),
+    };
+    for name in string_list(&args["container_names"]) {
+        match state.containers.get_mut(&name) {
+            Some(c) => c.network_state = network_state.clone(),
+            None => return Err(format!("Unknown container {}", name)),
+        }
+    }
+    return Ok(());
+}
+
+fn apply_partitions(state: &mut BlockadeState, args: &Value) -> Result<(), String> {
+    let groups: Vec<Vec<String>> = match args["partitions"].as_array() {
+        Some(groups) => groups.iter().map(string_list).collect(),
+        None => return Err("Missing partitions".into()),
+    };
+    // Like the daemon, containers left out of every group share one extra partition.
+    let leftover = groups.len() as u32 + 1;
+    for container in state.containers.values_mut() {
+        container.partition = leftover;
+    }
+    for (i, group) in groups.iter().enumerate() {
+        for name in group {
+            match state.containers.get_mut(name) {
+                Some(c) => c.partition = i as u32 + 1,
+                None => return Err(format!("Unknown container {}", name)),
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// Renders state the way the daemon does, with upper-case enum values.
+fn state_to_wire(state: &BlockadeState) -> Value {
+    let mut containers = serde_json::Map::new();
+    for (name, c) in state.containers.iter() {
+        let partition = if c.partition == 0 {
+            Value::Null
+        } else {
+            serde_json::json!(c.partition)
+        };
+        containers.insert(
+            name.clone(),
+            serde_json::json!({
+                "container_id": c.container_id,
+                "device": c.device,
+                "ip_address": c.ip_address.to_string(),
+                "name": c.name,
+                "network_state": c.network_state.to_str().to_uppercase(),
+                "partition": partition,
+                "status": c.status.to_str().to_uppercase(),
+            }),
+        );
+    }
+    return serde_json::json!({ "containers": containers });
+}
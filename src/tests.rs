@@ -1,7 +1,1706 @@
-//use blockade::*;
-//use common::*;
+use blockade::*;
+use common::*;
+use mock::*;
+use serde_json;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[test]
 fn it_works() {
     assert_eq!(2 + 2, 4);
 }
+
+fn container_in_partition(partition: u32) -> BlockadeContainerState {
+    return BlockadeContainerState {
+        partition,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn partitions_are_grouped_and_sorted() {
+    let mut state = BlockadeState::default();
+    state.containers.insert("c2".into(), container_in_partition(1));
+    state.containers.insert("c0".into(), container_in_partition(2));
+    state.containers.insert("c3".into(), container_in_partition(0));
+    state.containers.insert("c1".into(), container_in_partition(1));
+
+    assert_eq!(
+        state.partitions(),
+        vec![
+            vec![String::from("c0")],
+            vec![String::from("c1"), String::from("c2")],
+            vec![String::from("c3")],
+        ]
+    );
+}
+
+#[test]
+fn named_partitions_order_by_label() {
+    let mut named = NamedPartitions::default();
+    named.0.insert("minority".into(), vec!["c0".into()]);
+    named.0.insert("majority".into(), vec!["c1".into(), "c2".into()]);
+    assert_eq!(
+        named.to_args(),
+        vec![vec![String::from("c1"), String::from("c2")], vec![String::from("c0")]]
+    );
+}
+
+#[test]
+fn healed_state_is_not_partitioned() {
+    let mut state = BlockadeState::default();
+    state.containers.insert("c0".into(), container_in_partition(0));
+    state.containers.insert("c1".into(), container_in_partition(0));
+    assert!(!state.is_partitioned());
+
+    state.containers.insert("c0".into(), container_in_partition(1));
+    state.containers.insert("c1".into(), container_in_partition(2));
+    assert!(state.is_partitioned());
+}
+
+#[test]
+fn diff_ignores_volatile_fields() {
+    let mut before = BlockadeState::default();
+    before.containers.insert("c0".into(), container_in_partition(0));
+    before.containers.insert("c1".into(), container_in_partition(0));
+
+    let mut after = before.clone();
+    after.containers.get_mut("c0").unwrap().container_id = "new-id".into();
+    assert!(before.diff(&after).is_empty());
+
+    after.containers.get_mut("c1").unwrap().partition = 2;
+    after.containers.remove("c0");
+    after.containers.insert("c2".into(), container_in_partition(0));
+    let diff = before.diff(&after);
+    assert_eq!(diff.added, vec![String::from("c2")]);
+    assert_eq!(diff.removed, vec![String::from("c0")]);
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].partition, Some((0, 2)));
+    assert_eq!(diff.changed[0].status, None);
+}
+
+fn container_state_json(extra: &str) -> String {
+    return format!(
+        concat!(
+            r#"{{"container_id": "abc123", "name": "c0", "#,
+            r#""network_state": "NORMAL", "status": "UP"{}}}"#
+        ),
+        extra
+    );
+}
+
+#[test]
+fn container_state_ip_address_null() {
+    let json = container_state_json(r#", "ip_address": null, "partition": null"#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.ip_address, IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
+    assert_eq!(state.partition, 0);
+}
+
+#[test]
+fn container_state_ip_address_present() {
+    let json = container_state_json(r#", "ip_address": "10.0.0.5", "partition": 2"#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.ip_address, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)));
+    assert_eq!(state.partition, 2);
+}
+
+#[test]
+fn container_state_ip_address_v6() {
+    let json = container_state_json(r#", "ip_address": "fd00::5""#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        state.ip_address,
+        IpAddr::V6(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 5))
+    );
+}
+
+#[test]
+fn container_state_ip_address_missing() {
+    let json = container_state_json("");
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.ip_address, IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
+    assert_eq!(state.partition, 0);
+    assert_eq!(state.device, "");
+}
+
+#[test]
+fn container_state_defaults_non_critical_fields() {
+    let json = r#"{"container_id": "abc", "name": "c0", "status": "DOWN", "uptime": 12}"#;
+    let state: BlockadeContainerState = serde_json::from_str(json).unwrap();
+    assert_eq!(state.network_state, BlockadeNetStatus::Unknown);
+    assert_eq!(state.status, BlockadeContainerStatus::Down);
+}
+
+#[test]
+fn container_state_names_missing_required_field() {
+    let json = r#"{"name": "c0", "status": "UP", "network_state": "NORMAL"}"#;
+    let err = serde_json::from_str::<BlockadeContainerState>(json).unwrap_err();
+    assert!(err.to_string().contains("container_id"), "{}", err);
+}
+
+#[test]
+fn container_state_nullable_fields_serialize() {
+    let json = container_state_json(r#", "ip_address": "172.17.0.3", "partition": 1"#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    let value = serde_json::to_value(&state).unwrap();
+    assert_eq!(value["ip_address"], "172.17.0.3");
+    assert_eq!(value["partition"], 1);
+
+    let reparsed: BlockadeContainerState = serde_json::from_str(&container_state_json(&format!(
+        r#", "ip_address": {}, "partition": {}"#,
+        value["ip_address"], value["partition"]
+    )))
+    .unwrap();
+    assert_eq!(reparsed.ip_address, state.ip_address);
+    assert_eq!(reparsed.partition, state.partition);
+}
+
+#[test]
+fn status_enums_round_trip() {
+    let net_states = vec![
+        BlockadeNetStatus::Fast,
+        BlockadeNetStatus::Slow,
+        BlockadeNetStatus::Duplicate,
+        BlockadeNetStatus::Flaky,
+        BlockadeNetStatus::Unknown,
+    ];
+    for status in net_states {
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(serde_json::from_str::<BlockadeNetStatus>(&json).unwrap(), status);
+        let upper = status.to_str().to_uppercase();
+        assert_eq!(BlockadeNetStatus::from_str(&upper), status);
+    }
+    let statuses = vec![
+        BlockadeContainerStatus::Up,
+        BlockadeContainerStatus::Down,
+        BlockadeContainerStatus::Missing,
+    ];
+    for status in statuses {
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(serde_json::from_str::<BlockadeContainerStatus>(&json).unwrap(), status);
+    }
+
+    let state: BlockadeContainerState = serde_json::from_str(&container_state_json("")).unwrap();
+    let json = serde_json::to_string(&state).unwrap();
+    assert_eq!(serde_json::from_str::<BlockadeContainerState>(&json).unwrap(), state);
+}
+
+#[test]
+fn latency_spec_round_trips() {
+    let spec = LatencySpec::from_blockade_str("75ms 100ms distribution normal").unwrap();
+    assert_eq!(spec.delay, Duration::from_millis(75));
+    assert_eq!(spec.jitter, Some(Duration::from_millis(100)));
+    assert_eq!(spec.distribution, Some("normal".into()));
+    assert_eq!(spec.to_blockade_str(), "75ms 100ms distribution normal");
+
+    let spec = LatencySpec::from_blockade_str("1.5s").unwrap();
+    assert_eq!(spec.delay, Duration::from_millis(1500));
+    assert_eq!(spec.jitter, None);
+    assert_eq!(spec.to_blockade_str(), "1500ms");
+    assert_eq!(LatencySpec::from_blockade_str("2s").unwrap().to_blockade_str(), "2s");
+
+    assert!(LatencySpec::from_blockade_str("").is_err());
+    assert!(LatencySpec::from_blockade_str("75").is_err());
+    assert!(LatencySpec::from_blockade_str("75ms distribution").is_err());
+    assert!(LatencySpec::from_blockade_str("75ms 1ms 2ms").is_err());
+
+    let spec = LatencySpec::from_blockade_str("10ms reorder 25% 50%").unwrap();
+    assert_eq!(spec.reorder, Some("25% 50%".into()));
+    assert_eq!(spec.to_blockade_str(), "10ms reorder 25% 50%");
+    assert!(LatencySpec::from_blockade_str("10ms reorder").is_err());
+    assert!(LatencySpec::from_blockade_str("10ms reorder 25% 1ms").is_err());
+}
+
+#[test]
+fn make_net_reorder_extends_slow_spec() {
+    let (server, mut handler) = started_test_blockade();
+    let affected = handler.make_net_reorder("test", "25%").unwrap();
+    assert_eq!(affected.len(), 3);
+    assert_eq!(
+        handler.config["test"].network.slow,
+        "75ms 100ms distribution normal reorder 25%"
+    );
+    let state = server.blockade_state("test").unwrap();
+    assert_eq!(state.containers["c0"].network_state, BlockadeNetStatus::Slow);
+}
+
+#[test]
+fn net_driver_serde() {
+    let net: BlockadeNetConfig =
+        serde_json::from_str(r#"{"flaky": "10%", "slow": "75ms", "driver": "udn"}"#).unwrap();
+    assert_eq!(net.driver, NetDriver::Udn);
+    let net: BlockadeNetConfig =
+        serde_json::from_str(r#"{"flaky": "10%", "slow": "75ms", "driver": "macvlan"}"#).unwrap();
+    assert_eq!(net.driver, NetDriver::Other("macvlan".into()));
+    assert_eq!(serde_json::to_value(&net).unwrap()["driver"], "macvlan");
+    assert_eq!("UDN".parse::<NetDriver>().unwrap(), NetDriver::Udn);
+    assert_eq!("Bridge".parse::<NetDriver>().unwrap(), NetDriver::Bridge);
+}
+
+#[test]
+fn validate_rejects_unknown_net_drivers() {
+    let mut config = three_node_config();
+    config.network.driver = "udm".parse().unwrap();
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("udm"), "{}", err);
+
+    let (server, mut handler) = mock_handler();
+    assert!(handler.start_blockade("test", config, false).is_err());
+    assert!(server.requests().iter().all(|r| r.method == "GET"));
+}
+
+#[test]
+fn command_spec_accepts_both_forms() {
+    let shell: CommandSpec = serde_json::from_str(r#""cargo test""#).unwrap();
+    assert_eq!(shell, CommandSpec::Shell("cargo test".into()));
+    let args: CommandSpec = serde_json::from_str(r#"["cargo", "test"]"#).unwrap();
+    assert_eq!(args, CommandSpec::Args(vec!["cargo".into(), "test".into()]));
+    assert_eq!(serde_json::to_string(&args).unwrap(), r#"["cargo","test"]"#);
+}
+
+#[test]
+fn container_environment_is_optional() {
+    let json = r#"{"image": "rust", "hostname": "c0", "volumes": {}, "expose": [],
+        "ports": {}, "links": {}, "command": null}"#;
+    let container: BlockadeContainer = serde_json::from_str(json).unwrap();
+    assert!(container.environment.is_empty());
+
+    let mut container = BlockadeContainer::default();
+    container
+        .environment
+        .insert("CLUSTER_SEED".into(), "c0".into());
+    let value = serde_json::to_value(&container).unwrap();
+    assert_eq!(value["environment"]["CLUSTER_SEED"], "c0");
+}
+
+#[test]
+fn container_extra_options_pass_through() {
+    let json = r#"{"image": "rust", "hostname": "c0", "volumes": {}, "expose": [],
+        "ports": {}, "links": {}, "command": null, "cap_add": ["NET_ADMIN"], "net": "host"}"#;
+    let container: BlockadeContainer = serde_json::from_str(json).unwrap();
+    assert_eq!(container.extra["cap_add"], serde_json::json!(["NET_ADMIN"]));
+    assert_eq!(container.extra["net"], "host");
+
+    let value = serde_json::to_value(&container).unwrap();
+    assert_eq!(value["cap_add"][0], "NET_ADMIN");
+    assert!(value.get("extra").is_none());
+    assert!(BlockadeContainer::default().extra.is_empty());
+}
+
+fn three_node_config() -> BlockadeConfig {
+    return BlockadeConfig::with_n_containers(3, None);
+}
+
+/// A mock daemon and a handler talking to it.  Keep the server alive for the whole test.
+fn mock_handler() -> (MockBlockadeServer, BlockadeHandler) {
+    let server = MockBlockadeServer::start().unwrap();
+    let handler = BlockadeHandler::new(&server.url());
+    return (server, handler);
+}
+
+/// Like `mock_handler`, with blockade "test" already started from `three_node_config`.
+fn started_test_blockade() -> (MockBlockadeServer, BlockadeHandler) {
+    let (server, mut handler) = mock_handler();
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    return (server, handler);
+}
+
+#[test]
+fn with_n_containers_names_hosts() {
+    let template = BlockadeContainer {
+        image: String::from("alpine"),
+        ..Default::default()
+    };
+    let config = BlockadeConfig::with_n_containers(2, Some(template));
+    assert_eq!(config.containers.len(), 2);
+    assert_eq!(config.containers["c0"].hostname, "c0");
+    assert_eq!(config.containers["c1"].hostname, "c1");
+    assert_eq!(config.containers["c1"].image, "alpine");
+}
+
+#[test]
+fn expose_range_is_inclusive() {
+    let mut container = BlockadeContainer::default();
+    container.expose.push(7002);
+    container.expose_range(7000, 7003).expose_range(9000, 9000);
+    assert_eq!(container.expose, vec![7002, 7000, 7001, 7003, 9000]);
+    container.expose_range(10, 5);
+    assert_eq!(container.expose.len(), 5);
+}
+
+#[test]
+fn container_matches_state_by_name() {
+    let config = three_node_config();
+    let state = BlockadeContainerState {
+        name: String::from("c1"),
+        ..Default::default()
+    };
+    assert!(config.containers["c1"].matches_state("c1", &state));
+    assert!(!config.containers["c0"].matches_state("c0", &state));
+
+    // Without a hostname there is still a name to go by.
+    let bare = BlockadeContainer {
+        hostname: String::new(),
+        ..Default::default()
+    };
+    assert!(bare.matches_state("c1", &state));
+    assert!(!bare.matches_state("c0", &state));
+}
+
+#[test]
+fn out_of_range_ports_are_rejected_on_load() {
+    let container = |expose: &str, ports: &str| {
+        format!(
+            r#"{{"image": "rust", "hostname": "c0", "volumes": {{}}, "expose": {},
+            "ports": {}, "links": {{}}, "command": null}}"#,
+            expose, ports
+        )
+    };
+    let ok: BlockadeContainer =
+        serde_json::from_str(&container("[65535]", r#"{"8080": 80}"#)).unwrap();
+    assert_eq!(ok.ports[&8080], 80);
+
+    for json in [
+        container("[70000]", "{}"),
+        container("[-1]", "{}"),
+        container("[]", r#"{"70000": 80}"#),
+        container("[]", r#"{"8080": 70000}"#),
+    ]
+    .iter()
+    {
+        let err = serde_json::from_str::<BlockadeContainer>(json).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
+}
+
+#[test]
+fn validate_rejects_undefined_links() {
+    let mut config = three_node_config();
+    assert!(config.validate().is_ok());
+
+    config
+        .containers
+        .get_mut("c0")
+        .unwrap()
+        .links
+        .insert("c9".into(), "db".into());
+    match config.validate() {
+        Err(BlockadeError::OtherError(msg)) => {
+            assert!(msg.contains("c0") && msg.contains("c9"), "{}", msg)
+        }
+        other => panic!("Expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn validate_rejects_clashing_host_ports() {
+    let mut config = three_node_config();
+    config.containers.get_mut("c0").unwrap().ports.insert(8080, 80);
+    config.containers.get_mut("c1").unwrap().ports.insert(8080, 80);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn mock_server_lifecycle() {
+    let (server, mut handler) = mock_handler();
+
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    assert_eq!(
+        handler.get_all_containers("test").unwrap(),
+        vec![String::from("c0"), String::from("c1"), String::from("c2")]
+    );
+
+    let killed = handler.kill_container("test", "c1").unwrap();
+    assert_eq!(killed.status, BlockadeContainerStatus::Down);
+    let started = handler
+        .start_container_and_wait("test", "c1", Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(started.status, BlockadeContainerStatus::Up);
+
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    assert!(handler.is_partitioned("test").unwrap());
+    assert_eq!(
+        handler.get_partitions("test").unwrap(),
+        vec![vec![String::from("c0")], vec![String::from("c1"), String::from("c2")]]
+    );
+    handler.heal_partitions("test").unwrap();
+    assert!(!handler.is_partitioned("test").unwrap());
+
+    assert!(handler.destroy_blockade("test").unwrap());
+    assert!(server.blockade_state("test").is_none());
+    assert!(!handler.destroy_blockade("test").unwrap());
+}
+
+#[test]
+fn mock_server_surfaces_server_errors() {
+    let (server, mut handler) = started_test_blockade();
+
+    server.fail_next(500, "boom");
+    match handler.kill_container("test", "c0") {
+        Err(BlockadeError::ServerError(ref body)) => assert_eq!(body, "boom"),
+        other => panic!("Expected a server error, got {:?}", other),
+    }
+}
+
+#[test]
+fn observer_sees_every_exchange() {
+    let (_server, mut handler) = mock_handler();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    handler.set_observer(Box::new(move |url, body, status, _response| {
+        sink.lock()
+            .unwrap()
+            .push((url.to_owned(), body.to_owned(), status));
+    }));
+
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    handler.kill_container("test", "c0").unwrap();
+
+    let seen = seen.lock().unwrap();
+    let action = seen
+        .iter()
+        .find(|(url, _, _)| url.ends_with("/blockade/test/action"))
+        .expect("action request was not observed");
+    assert!(action.1.contains("\"kill\""));
+    assert_eq!(action.2, 204);
+}
+
+#[test]
+fn update_network_config_preserves_degraded_containers() {
+    let (server, mut handler) = started_test_blockade();
+    handler.make_net_unreliable("test").unwrap();
+
+    let net = BlockadeNetConfig {
+        flaky: String::from("30%"),
+        ..Default::default()
+    };
+    handler.update_network_config("test", net).unwrap();
+
+    assert_eq!(handler.config["test"].network.flaky, "30%");
+    let state = server.blockade_state("test").unwrap();
+    for container in state.containers.values() {
+        assert_eq!(container.network_state, BlockadeNetStatus::Flaky);
+    }
+}
+
+#[test]
+fn update_network_config_restores_partitions_and_killed_containers() {
+    let (server, mut handler) = started_test_blockade();
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    handler.kill_container("test", "c2").unwrap();
+    let before = server.blockade_state("test").unwrap();
+
+    handler.update_network_config("test", BlockadeNetConfig::default()).unwrap();
+
+    let after = server.blockade_state("test").unwrap();
+    assert_eq!(after.partitions(), before.partitions());
+    assert_eq!(after.containers["c2"].status, BlockadeContainerStatus::Down);
+    assert_eq!(after.containers["c1"].status, BlockadeContainerStatus::Up);
+    // The containers really were re-created.
+    let recreated = server.requests().iter().filter(|r| r.method == "DELETE").count();
+    assert_eq!(recreated, 1);
+}
+
+#[test]
+fn start_blockade_propagates_non_conflict_errors() {
+    let (server, mut handler) = mock_handler();
+
+    server.fail_next(500, "docker exploded");
+    assert!(handler.start_blockade("test", three_node_config(), true).is_err());
+    assert!(server.blockade_state("test").is_none());
+
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    let state = handler.start_blockade("test", three_node_config(), true).unwrap();
+    assert_eq!(state.containers.len(), 3);
+}
+
+#[test]
+fn rejected_start_keeps_the_stored_config() {
+    let (server, mut handler) = started_test_blockade();
+
+    let other = BlockadeConfig::with_n_containers(5, None);
+    assert!(handler.start_blockade("test", other.clone(), false).is_err());
+    server.fail_next(500, "docker exploded");
+    assert!(handler.start_blockade("test", other, true).is_err());
+    assert_eq!(handler.clone_config("test"), Some(three_node_config()));
+    server.fail_next(500, "docker exploded");
+    assert!(handler.start_blockade("failed", three_node_config(), false).is_err());
+    assert_eq!(handler.clone_config("failed"), None);
+}
+
+#[test]
+fn start_blockade_detects_conflict_variants() {
+    let (server, mut handler) = started_test_blockade();
+    match handler.start_blockade("test", three_node_config(), false) {
+        Err(BlockadeError::Conflict(_)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    for &(status, body) in [(409, "Conflict"), (500, "Blockade 'test' ALREADY EXISTS")].iter() {
+        server.fail_next(status, body);
+        let state = handler.start_blockade("test", three_node_config(), true).unwrap();
+        assert_eq!(state.containers.len(), 3);
+    }
+
+    // Not a clash of blockade names, so nothing is torn down.
+    let deletes = |server: &MockBlockadeServer| {
+        server.requests().iter().filter(|r| r.method == "DELETE").count()
+    };
+    let before = deletes(&server);
+    for &(status, body) in [
+        (500, "network with name blockade-test already exists"),
+        (404, "Blockade name already exists"),
+    ]
+    .iter()
+    {
+        server.fail_next(status, body);
+        match handler.start_blockade("test", three_node_config(), true) {
+            Err(BlockadeError::ServerError(ref text)) => assert_eq!(text, body),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+    assert_eq!(deletes(&server), before);
+}
+
+#[test]
+fn kill_one_except_spares_excluded_containers() {
+    let (_server, mut handler) = started_test_blockade();
+
+    let leader = vec![String::from("c0"), String::from("c1")];
+    assert_eq!(handler.kill_one_except("test", &leader).unwrap(), "c2");
+
+    let everyone = handler.get_all_containers("test").unwrap();
+    match handler.kill_one_except("test", &everyone) {
+        Err(BlockadeError::EmptyBlockade(ref name)) => assert_eq!(name, "test"),
+        other => panic!("Expected EmptyBlockade, got {:?}", other),
+    }
+}
+
+#[test]
+fn get_blockade_raw_returns_untyped_json() {
+    let (_server, mut handler) = started_test_blockade();
+
+    let raw = handler.get_blockade_raw("test").unwrap();
+    assert_eq!(raw["containers"]["c0"]["status"], "UP");
+}
+
+#[test]
+fn dry_run_sends_nothing() {
+    let server = MockBlockadeServer::start().unwrap();
+    let mut handler = BlockadeHandler::dry_run(&server.url());
+
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    let killed = handler.kill_one("test").unwrap();
+    assert!(handler.state["test"].containers.contains_key(&killed));
+    handler.destroy_blockade("test").unwrap();
+
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn scoped_blockade_is_destroyed_on_drop() {
+    let (server, mut handler) = mock_handler();
+    {
+        let guard = handler
+            .start_blockade_scoped("test", three_node_config())
+            .unwrap();
+        assert_eq!(guard.name(), "test");
+        assert!(server.blockade_state("test").is_some());
+    }
+    assert!(server.blockade_state("test").is_none());
+}
+
+#[test]
+fn blockade_list_accepts_object_and_array() {
+    assert_eq!(
+        parse_blockade_list(r#"{"blockades": ["a", "b"]}"#, "blockades").unwrap(),
+        vec![String::from("a"), String::from("b")]
+    );
+    assert_eq!(
+        parse_blockade_list(r#"["a", "b"]"#, "blockades").unwrap(),
+        vec![String::from("a"), String::from("b")]
+    );
+    for raw in [r#"{}"#, r#"{"names": ["a"]}"#].iter() {
+        match parse_blockade_list(raw, "blockades") {
+            Err(BlockadeError::BadResponse { ref source, .. }) => {
+                assert!(source.to_string().contains("`blockades`"), "{}", source)
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+    assert!(parse_blockade_list(r#""nope""#, "blockades").is_err());
+    assert_eq!(
+        parse_blockade_list(r#"{"names": ["a"], "count": 1}"#, "names").unwrap(),
+        vec!["a"]
+    );
+}
+
+#[test]
+fn fetch_state_collects_per_blockade_errors() {
+    let (server, mut handler) = mock_handler();
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    handler.start_blockade("b", three_node_config(), false).unwrap();
+    handler.state.clear();
+
+    // The daemon lists a blockade it can no longer return.
+    server.fail_next(200, r#"{"blockades": ["a", "gone", "b"]}"#);
+    let errors = handler.fetch_state(Duration::from_secs(5)).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "gone");
+    assert!(handler.state.contains_key("a"));
+    assert!(handler.state.contains_key("b"));
+}
+
+#[test]
+fn get_container_net_status_follows_net_commands() {
+    let (_server, mut handler) = started_test_blockade();
+    let status = handler.get_container_net_status("test", "c1").unwrap();
+    assert_eq!(status, BlockadeNetStatus::Fast);
+
+    let affected = handler.make_net_unreliable("test").unwrap();
+    assert_eq!(affected, vec!["c0", "c1", "c2"]);
+    let status = handler.get_container_net_status("test", "c1").unwrap();
+    assert_eq!(status, BlockadeNetStatus::Flaky);
+    assert!(handler.get_container_net_status("test", "missing").is_err());
+}
+
+#[test]
+fn state_display_is_a_sorted_table() {
+    let mut state = BlockadeState::default();
+    for (name, partition) in [("c1", 2), ("c0", 1)].iter() {
+        let mut container = container_in_partition(*partition);
+        container.name = String::from(*name);
+        state.containers.insert(String::from(*name), container);
+    }
+    let rendered = state.to_string();
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("NAME"));
+    assert!(lines[1].starts_with("c0  "));
+    assert!(lines[2].starts_with("c1  "));
+    assert_eq!(
+        state.containers["c0"].to_string(),
+        "c0 missing partition=1 ip=127.0.0.2 net=unknown"
+    );
+}
+
+#[test]
+fn wait_for_partition_matches_regardless_of_order() {
+    let (_server, mut handler) = started_test_blockade();
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c2".into(), "c1".into()]])
+        .unwrap();
+
+    let expected = vec![vec!["c2".into(), "c1".into()], vec!["c0".into()]];
+    let partitions = handler
+        .wait_for_partition("test", expected, Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(partitions.len(), 2);
+
+    let err = handler
+        .wait_for_partition("test", vec![vec!["c0".into()]], Duration::from_millis(0))
+        .unwrap_err();
+    assert!(err.to_string().contains("last saw"));
+}
+
+#[test]
+fn ensure_blockade_only_creates_when_absent() {
+    let (server, mut handler) = mock_handler();
+    handler.ensure_blockade("test", three_node_config()).unwrap();
+    handler.ensure_blockade("test", three_node_config()).unwrap();
+    let creates = server
+        .requests()
+        .iter()
+        .filter(|r| r.method == "POST" && r.path == "/blockade/test")
+        .count();
+    assert_eq!(creates, 1);
+
+    let err = handler
+        .ensure_blockade("test", BlockadeConfig::with_n_containers(2, None))
+        .unwrap_err();
+    assert!(err.to_string().contains("already exists"));
+    assert!(server.blockade_state("test").is_some());
+}
+
+#[test]
+fn container_state_started_at_is_optional() {
+    let json = container_state_json(r#", "started_at": "2018-03-01T12:00:00Z""#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.started_at, Some(String::from("2018-03-01T12:00:00Z")));
+
+    let state: BlockadeContainerState = serde_json::from_str(&container_state_json("")).unwrap();
+    assert!(state.started_at.is_none());
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn container_uptime_parses_started_at() {
+    let json = container_state_json(r#", "started_at": "2018-03-01T12:00:00Z""#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert_eq!(state.started_at().unwrap().timestamp(), 1_519_905_600);
+    assert!(state.uptime().unwrap() > ::chrono::Duration::zero());
+
+    let json = container_state_json(r#", "started_at": "yesterday""#);
+    let state: BlockadeContainerState = serde_json::from_str(&json).unwrap();
+    assert!(state.started_at().is_none());
+    assert!(state.uptime().is_none());
+}
+
+#[test]
+fn chaos_starts_and_stops() {
+    let (server, mut handler) = started_test_blockade();
+
+    let config = ChaosConfig {
+        event_set: vec![ChaosEvent::Partition, ChaosEvent::Flaky],
+        ..Default::default()
+    };
+    handler.start_chaos("test", config.clone()).unwrap();
+    assert_eq!(server.chaos_config("test"), Some(config));
+    let body = server.requests().last().unwrap().body.clone();
+    assert!(body.contains(r#""event_set":["PARTITION","FLAKY"]"#), "{}", body);
+
+    handler.stop_chaos("test").unwrap();
+    assert!(server.chaos_config("test").is_none());
+    assert!(handler.stop_chaos("test").is_err());
+}
+
+#[test]
+fn mock_server_addresses_large_blockades() {
+    let (server, mut handler) = mock_handler();
+    handler
+        .start_blockade("big", BlockadeConfig::with_n_containers(300, None), false)
+        .unwrap();
+    let state = server.blockade_state("big").unwrap();
+    let mut ips: Vec<IpAddr> = state.containers.values().map(|c| c.ip_address).collect();
+    ips.sort();
+    ips.dedup();
+    assert_eq!(ips.len(), 300);
+    assert_eq!(ips[299], IpAddr::V4(Ipv4Addr::new(172, 17, 1, 45)));
+}
+
+#[test]
+fn ping_reports_daemon_health() {
+    let server = MockBlockadeServer::start().unwrap();
+    BlockadeHandler::ping(&server.url()).unwrap();
+    server.fail_next(500, "down for maintenance");
+    assert!(BlockadeHandler::ping(&server.url()).is_err());
+}
+
+#[test]
+fn destroy_all_removes_every_blockade() {
+    let (server, mut handler) = mock_handler();
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    handler.start_blockade("b", three_node_config(), false).unwrap();
+
+    let mut removed = handler.destroy_all().unwrap();
+    removed.sort();
+    assert_eq!(removed, vec!["a", "b"]);
+    assert!(server.blockade_state("a").is_none());
+    assert!(handler.blockades.is_empty());
+    assert!(handler.state.is_empty());
+}
+
+// A `GET /blockade/<name>` response in the daemon's format, including the `holy` and
+// `neutral` flags this crate doesn't model.
+const DAEMON_STATE_FIXTURE: &str = r#"{"containers": {"c1": {
+    "container_id": "4f2b1d9a7c3e", "device": "veth5a1b2c3", "holy": false,
+    "ip_address": "172.17.0.2", "name": "c1", "neutral": false,
+    "network_state": "SLOW", "partition": 2, "status": "UP"}}}"#;
+
+// A container definition as posted to `POST /blockade/<name>`.
+const DAEMON_CONTAINER_FIXTURE: &str = r#"{"image": "ubuntu:trusty", "hostname": "c1",
+    "volumes": {"/tmp/data": "/data"}, "expose": [10000], "ports": {"8080": 80},
+    "links": {"c2": "db"}, "command": "/bin/sleep 300000",
+    "environment": {"SEED": "c2"}}"#;
+
+#[test]
+fn container_state_fields_match_daemon_names() {
+    let state: BlockadeState = serde_json::from_str(DAEMON_STATE_FIXTURE).unwrap();
+    let c1 = &state.containers["c1"];
+    assert_eq!(c1.container_id, "4f2b1d9a7c3e");
+    assert_eq!(c1.device, "veth5a1b2c3");
+    assert_eq!(c1.ip_address, IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2)));
+    assert_eq!(c1.name, "c1");
+    assert_eq!(c1.network_state, BlockadeNetStatus::Slow);
+    assert_eq!(c1.partition, 2);
+    assert_eq!(c1.status, BlockadeContainerStatus::Up);
+}
+
+#[test]
+fn container_config_fields_match_daemon_names() {
+    let container: BlockadeContainer = serde_json::from_str(DAEMON_CONTAINER_FIXTURE).unwrap();
+    // Anything misnamed would land in `extra` instead of its field.
+    assert!(container.extra.is_empty(), "{:?}", container.extra);
+    assert_eq!(container.image, "ubuntu:trusty");
+    assert_eq!(container.hostname, "c1");
+    assert_eq!(container.volumes["/tmp/data"], "/data");
+    assert_eq!(container.expose, vec![10000]);
+    assert_eq!(container.ports[&8080], 80);
+    assert_eq!(container.links["c2"], "db");
+    assert_eq!(container.command, Some("/bin/sleep 300000".into()));
+    assert_eq!(container.environment["SEED"], "c2");
+
+    let original: serde_json::Value = serde_json::from_str(DAEMON_CONTAINER_FIXTURE).unwrap();
+    assert_eq!(serde_json::to_value(&container).unwrap(), original);
+}
+
+#[test]
+fn all_containers_walks_every_blockade() {
+    let mut handler = BlockadeHandler::dry_run("http://127.0.0.1:5000");
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    handler.start_blockade("b", BlockadeConfig::with_n_containers(2, None), false).unwrap();
+    let mut seen: Vec<(&str, &str)> = handler.all_containers().map(|(b, c, _)| (b, c)).collect();
+    seen.sort();
+    assert_eq!(seen, vec![("a", "c0"), ("a", "c1"), ("a", "c2"), ("b", "c0"), ("b", "c1")]);
+}
+
+#[test]
+fn summary_counts_cached_state() {
+    let (_server, mut handler) = started_test_blockade();
+    handler.stop_container("test", "c0").unwrap();
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+
+    let summary = handler.summary();
+    let counts = &summary.blockades["test"];
+    assert_eq!((counts.up, counts.down, counts.missing), (2, 1, 0));
+    assert_eq!(counts.partitions, 2);
+    assert_eq!(summary.partitions, 2);
+    let json = serde_json::to_value(&summary).unwrap();
+    assert_eq!(json["blockades"]["test"]["down"], 1);
+}
+
+#[test]
+fn strict_partitions_require_every_container_once() {
+    let (_server, mut handler) = started_test_blockade();
+
+    let err = match handler
+        .make_strict_partitions("test", vec![vec!["c0".into()], vec!["c0".into(), "c9".into()]])
+    {
+        Err(BlockadeError::OtherError(e)) => e,
+        other => panic!("Unexpected result {:?}", other),
+    };
+    assert!(err.contains(r#"missing ["c1", "c2"]"#), "{}", err);
+    assert!(err.contains(r#"repeated ["c0"]"#), "{}", err);
+    assert!(err.contains(r#"unknown ["c9"]"#), "{}", err);
+    assert!(!handler.is_partitioned("test").unwrap());
+
+    handler
+        .make_strict_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    assert!(handler.is_partitioned("test").unwrap());
+}
+
+#[test]
+fn timeouts_apply_per_request_kind() {
+    let server = MockBlockadeServer::start().unwrap();
+    let timeouts = TimeoutConfig {
+        read: Duration::from_millis(200),
+        mutate: Duration::from_secs(5),
+        ..Default::default()
+    };
+    let mut handler = BlockadeHandler::with_timeouts(&server.url(), timeouts).unwrap();
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+
+    // Slower than a read may take, but well within the limit for mutations: the kill goes
+    // through and the refresh that follows it times out.
+    server.delay_responses(Duration::from_millis(600));
+    assert!(handler.kill_container("test", "c0").is_err());
+    let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+    assert_eq!(methods[methods.len() - 2..], ["POST".to_owned(), "GET".to_owned()]);
+
+    let state = server.blockade_state("test").unwrap();
+    assert_eq!(state.containers["c0"].status, BlockadeContainerStatus::Down);
+}
+
+#[test]
+fn start_blockade_from_reuses_stored_config() {
+    let (_server, mut handler) = mock_handler();
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    assert_eq!(handler.clone_config("a"), Some(three_node_config()));
+    assert!(handler.clone_config("other").is_none());
+
+    let state = handler.start_blockade_from("b", "a").unwrap();
+    assert_eq!(state.containers.len(), 3);
+    assert_eq!(handler.config["b"], handler.config["a"]);
+    assert!(handler.start_blockade_from("c", "other").is_err());
+}
+
+#[test]
+fn fetch_state_for_refreshes_only_named_blockades() {
+    let (_server, mut handler) = mock_handler();
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    handler.start_blockade("b", three_node_config(), false).unwrap();
+    handler.state.clear();
+
+    let errors = handler.fetch_state_for(&["a", "gone"]).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "gone");
+    assert!(handler.state.contains_key("a"));
+    assert!(!handler.state.contains_key("b"));
+}
+
+#[test]
+fn wait_for_destroyed_polls_until_gone() {
+    let (_server, mut handler) = started_test_blockade();
+    assert!(handler.blockade_exists("test").unwrap());
+    assert!(handler.wait_for_destroyed("test", Duration::from_millis(0)).is_err());
+
+    handler.destroy_blockade("test").unwrap();
+    handler.wait_for_destroyed("test", Duration::from_secs(1)).unwrap();
+    assert!(!handler.blockade_exists("test").unwrap());
+}
+
+#[test]
+fn blockade_exists_forgets_blockades_destroyed_elsewhere() {
+    let (server, mut handler) = started_test_blockade();
+    handler.tag_containers("test", "leader", vec!["c0".into()]);
+    BlockadeHandler::new(&server.url()).destroy_blockade("test").unwrap();
+
+    assert!(!handler.blockade_exists("test").unwrap());
+    assert!(!handler.state.contains_key("test"));
+    assert!(!handler.blockades.contains(&String::from("test")));
+    assert!(handler.clone_config("test").is_none());
+    assert!(!handler.tags.contains_key("test"));
+}
+
+#[cfg(all(unix, feature = "unix-socket"))]
+#[test]
+fn unix_socket_transport() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    let path = std::env::temp_dir().join(format!("blockade-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let server = std::thread::spawn(move || {
+        let mut request_lines = Vec::new();
+        for stream in listener.incoming().take(2) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+            }
+            let response = if request_line.starts_with("GET /blockade ") {
+                // Chunked, as some daemons answer.
+                String::from(
+                    "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     f\r\n{\"blockades\": [\r\n6\r\n\"x\"]}\r\n0\r\n\r\n",
+                )
+            } else {
+                let body = r#"{"containers": {}}"#;
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+            request_lines.push(request_line.trim_end().to_owned());
+        }
+        request_lines
+    });
+
+    let handler = BlockadeHandler::with_unix_socket(&path);
+    assert_eq!(handler.blockades, vec!["x"]);
+    assert!(handler.state["x"].containers.is_empty());
+    assert_eq!(
+        server.join().unwrap(),
+        vec!["GET /blockade HTTP/1.1", "GET /blockade/x HTTP/1.1"]
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(all(unix, feature = "docker-logs"))]
+#[test]
+fn container_logs_come_from_the_docker_socket() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+
+    let (server, mut handler) = started_test_blockade();
+
+    let path = std::env::temp_dir().join(format!("docker-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let docker = std::thread::spawn(move || {
+        let mut stream = listener.incoming().next().unwrap().unwrap();
+        let mut request_line = String::new();
+        BufReader::new(stream.try_clone().unwrap()).read_line(&mut request_line).unwrap();
+        let mut body = Vec::new();
+        for &(stream, text) in &[(1u8, "up\n"), (2, "oops\n"), (1, "retrying\nok\n")] {
+            body.extend_from_slice(&[stream, 0, 0, 0, 0, 0, 0, text.len() as u8]);
+            body.extend_from_slice(text.as_bytes());
+        }
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+        stream.write_all(&body).unwrap();
+        request_line
+    });
+
+    let lines = handler.get_container_logs("test", "c1", Some(10), &path).unwrap();
+    let lines: Vec<(LogStream, &str)> = lines.iter().map(|l| (l.stream, &l.text[..])).collect();
+    assert_eq!(
+        lines,
+        vec![
+            (LogStream::Stdout, "up"),
+            (LogStream::Stderr, "oops"),
+            (LogStream::Stdout, "retrying"),
+            (LogStream::Stdout, "ok"),
+        ]
+    );
+    assert_eq!(
+        docker.join().unwrap().trim_end(),
+        "GET /containers/mock-test-c1/logs?stdout=1&stderr=1&tail=10 HTTP/1.1"
+    );
+    let _ = std::fs::remove_file(&path);
+
+    // Dry runs have no container ids, and are refused before the socket is touched.
+    let mut dry = BlockadeHandler::dry_run(&server.url());
+    dry.state.insert("test".into(), handler.state["test"].clone());
+    dry.state.get_mut("test").unwrap().containers.get_mut("c1").unwrap().container_id.clear();
+    let err = dry.get_container_logs("test", "c1", None, &path).unwrap_err();
+    assert!(err.to_string().contains("no container_id"), "{}", err);
+}
+
+#[test]
+fn batch_operations_return_affected_containers() {
+    let (_server, mut handler) = started_test_blockade();
+    let all: Vec<String> = vec!["c0".into(), "c1".into(), "c2".into()];
+    assert_eq!(handler.kill_all("test").unwrap(), all);
+    assert_eq!(handler.state["test"].counts().down, 3);
+    let some = vec![String::from("c0"), String::from("c2")];
+    assert_eq!(handler.start_containers("test", &some).unwrap(), some);
+    assert_eq!(handler.state["test"].counts().down, 1);
+    assert_eq!(handler.restart_containers("test", &all).unwrap(), all);
+    assert_eq!(handler.state["test"].counts().up, 3);
+}
+
+#[test]
+fn user_agent_is_sent_with_every_request() {
+    let server = MockBlockadeServer::start().unwrap();
+    let mut handler = BlockadeHandler::with_user_agent(&server.url(), "chaos-suite/1.0").unwrap();
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    let requests = server.requests();
+    assert!(!requests.is_empty());
+    for request in requests {
+        assert_eq!(request.headers["user-agent"], "chaos-suite/1.0");
+    }
+    assert!(BlockadeHandler::with_user_agent(&server.url(), "bad\nagent").is_err());
+}
+
+#[test]
+fn container_device_is_reported() {
+    let (_server, mut handler) = started_test_blockade();
+    let device = handler.get_container_device("test", "c1").unwrap();
+    assert_eq!(device, Some(String::from("veth1")));
+    assert!(handler.get_container_device("test", "c9").is_err());
+}
+
+#[test]
+fn partition_net_only_touches_one_side() {
+    let (_server, mut handler) = started_test_blockade();
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    let slowed = handler.set_partition_net("test", 2, BlockadeNetStatus::Slow).unwrap();
+    assert_eq!(slowed, vec!["c1", "c2"]);
+    let state = &handler.state["test"];
+    assert_eq!(state.containers["c0"].network_state, BlockadeNetStatus::Fast);
+    assert_eq!(state.containers["c1"].network_state, BlockadeNetStatus::Slow);
+    assert!(handler.set_partition_net("test", 7, BlockadeNetStatus::Slow).is_err());
+}
+
+#[test]
+fn simple_containers_fill_in_defaults() {
+    let container = BlockadeContainer::from(("rust", "cargo test"));
+    assert_eq!(container.image, "rust");
+    assert_eq!(container.command, Some(CommandSpec::Shell("cargo test".into())));
+    assert_eq!(container.hostname, BlockadeContainer::default().hostname);
+    let bare = BlockadeContainer::simple("alpine", None);
+    assert_eq!(bare.image, "alpine");
+    assert_eq!(bare.command, None);
+}
+
+#[test]
+fn healing_an_unpartitioned_blockade_succeeds() {
+    let (server, mut handler) = started_test_blockade();
+    server.fail_next(400, "No partitions to restore");
+    handler.heal_partitions("test").unwrap();
+    server.fail_next(400, "Invalid network state");
+    assert!(handler.heal_partitions("test").is_err());
+    server.fail_next(404, "No partitions: blockade not found");
+    assert!(handler.heal_partitions("test").is_err());
+}
+
+struct CannedTransport {
+    sent: Arc<Mutex<Vec<String>>>,
+    headers: Arc<Mutex<Vec<reqwest::header::HeaderMap>>>,
+}
+
+impl BlockadeTransport for CannedTransport {
+    fn send(
+        &self,
+        request: &reqwest::Request,
+        _body: Option<&str>,
+    ) -> Result<(u16, String), BlockadeError> {
+        let line = format!("{} {}", request.method(), request.url().path());
+        self.sent.lock().unwrap().push(line);
+        self.headers.lock().unwrap().push(request.headers().clone());
+        if request.url().path() == "/blockade" {
+            return Ok((200, r#"{"blockades": ["canned"]}"#.into()));
+        }
+        return Ok((200, DAEMON_STATE_FIXTURE.into()));
+    }
+}
+
+#[test]
+fn custom_transport_replaces_http() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let headers = Arc::new(Mutex::new(Vec::new()));
+    let transport = CannedTransport {
+        sent: sent.clone(),
+        headers: headers.clone(),
+    };
+    let mut handler = BlockadeHandler::with_transport("http://nowhere", Box::new(transport));
+    assert_eq!(handler.blockades, vec!["canned"]);
+    assert_eq!(handler.state["canned"].containers["c1"].status, BlockadeContainerStatus::Up);
+
+    // Auth and extra headers reach a custom transport like any other.
+    handler.auth = Some(AuthScheme::Bearer("s3cret".into()));
+    handler.headers.insert(
+        reqwest::header::USER_AGENT,
+        reqwest::header::HeaderValue::from_static("chaos-suite/1.0"),
+    );
+    handler.make_net_fast("canned").unwrap();
+    assert!(sent.lock().unwrap().contains(&"POST /blockade/canned/network_state".into()));
+    let last = headers.lock().unwrap().last().cloned().unwrap();
+    assert_eq!(last["authorization"], "Bearer s3cret");
+    assert_eq!(last["user-agent"], "chaos-suite/1.0");
+}
+
+#[test]
+fn poll_state_yields_only_changes() {
+    let (server, mut handler) = started_test_blockade();
+    let mut other = BlockadeHandler::new(&server.url());
+    let changes: Vec<BlockadeState> = {
+        let mut poller = handler.poll_state("test", Duration::from_millis(10));
+        let first = poller.next().unwrap().unwrap();
+        other.kill_container("test", "c0").unwrap();
+        let second = poller.next().unwrap().unwrap();
+        vec![first, second]
+    };
+    assert_eq!(changes[0].containers["c0"].status, BlockadeContainerStatus::Up);
+    assert_eq!(changes[1].containers["c0"].status, BlockadeContainerStatus::Down);
+    assert_eq!(handler.state["test"], changes[1]);
+
+    let mut missing = handler.poll_state("nope", Duration::from_millis(10));
+    assert!(missing.next().unwrap().is_err());
+}
+
+#[test]
+fn empty_get_responses_are_not_json_errors() {
+    let (server, mut handler) = started_test_blockade();
+    server.fail_next(204, "");
+    assert_eq!(handler.get_blockade_raw("test").unwrap(), serde_json::Value::Null);
+    server.fail_next(204, "");
+    handler.fetch_state_for(&["test"]).unwrap();
+    assert!(handler.state["test"].containers.is_empty());
+    assert_eq!(parse_blockade_list("", "blockades").unwrap(), Vec::<String>::new());
+    assert_eq!(parse_blockade_list(" \n", "blockades").unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn enums_parse_without_panicking() {
+    assert_eq!("kill".parse::<BlockadeCommand>().unwrap(), BlockadeCommand::Kill);
+    assert_eq!("NORMAL".parse::<BlockadeNetStatus>().unwrap(), BlockadeNetStatus::Fast);
+    assert_eq!("Down".parse::<BlockadeContainerStatus>().unwrap(), BlockadeContainerStatus::Down);
+    assert!("explode".parse::<BlockadeCommand>().is_err());
+    assert!("sideways".parse::<BlockadeNetStatus>().is_err());
+    let json = DAEMON_STATE_FIXTURE.replace(r#""status": "UP""#, r#""status": "EXPLODED""#);
+    assert!(serde_json::from_str::<BlockadeState>(&json).is_err());
+}
+
+#[test]
+fn enums_display_their_wire_names() {
+    assert_eq!(BlockadeCommand::Restart.to_string(), "restart");
+    assert_eq!(format!("{}", BlockadeNetStatus::Flaky), "flaky");
+    assert_eq!(format!("[{:<6}]", BlockadeContainerStatus::Up), "[up    ]");
+    for s in &["start", "stop", "restart", "kill"] {
+        assert_eq!(s.parse::<BlockadeCommand>().unwrap().to_string(), *s);
+    }
+}
+
+#[test]
+fn tagged_containers_are_commanded_together() {
+    let (_server, mut handler) = started_test_blockade();
+    handler.tag_containers("test", "followers", vec!["c1".into(), "c2".into()]);
+    assert_eq!(handler.kill_tagged("test", "followers").unwrap(), vec!["c1", "c2"]);
+    let state = &handler.state["test"];
+    assert_eq!(state.containers["c0"].status, BlockadeContainerStatus::Up);
+    assert_eq!(state.containers["c2"].status, BlockadeContainerStatus::Down);
+    handler.start_tagged("test", "followers").unwrap();
+    assert_eq!(handler.state["test"].counts().up, 3);
+    assert!(handler.kill_tagged("test", "leaders").is_err());
+    assert!(handler.get_tagged("other", "followers").is_err());
+}
+
+#[test]
+fn heal_fully_resets_partitions_and_network() {
+    let (_server, mut handler) = started_test_blockade();
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    handler.make_net_unreliable("test").unwrap();
+    handler.heal_fully("test").unwrap();
+    let state = &handler.state["test"];
+    assert!(!state.is_partitioned());
+    for c in state.containers.values() {
+        assert_eq!(c.network_state, BlockadeNetStatus::Fast);
+    }
+}
+
+#[test]
+fn unparseable_responses_keep_the_raw_text() {
+    let (server, mut handler) = started_test_blockade();
+    server.fail_next(200, r#"{"containers": "nope"}"#);
+    match handler.fetch_state_for(&["test"]) {
+        Err(mut errors) => match errors.remove(0).1 {
+            BlockadeError::BadResponse { raw, .. } => assert_eq!(raw, r#"{"containers": "nope"}"#),
+            e => panic!("unexpected error {}", e),
+        },
+        Ok(()) => panic!("parsed a bad response"),
+    }
+}
+
+#[test]
+fn kill_one_in_each_hits_every_blockade() {
+    let (_server, mut handler) = mock_handler();
+    handler.start_blockade("a", three_node_config(), false).unwrap();
+    handler.start_blockade("b", three_node_config(), false).unwrap();
+    let killed = handler.kill_one_in_each(&["a", "b"]).unwrap();
+    assert_eq!(killed.len(), 2);
+    for (name, container) in killed.iter() {
+        let state = &handler.state[name];
+        assert_eq!(state.containers[container].status, BlockadeContainerStatus::Down);
+        assert_eq!(state.counts().down, 1);
+    }
+    assert!(handler.kill_one_in_each(&["a", "missing"]).is_err());
+}
+
+#[test]
+fn daemon_info_probes_chaos_support() {
+    let (server, mut handler) = mock_handler();
+    assert_eq!(handler.get_daemon_info().unwrap(), DaemonInfo::default());
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    let info = handler.get_daemon_info().unwrap();
+    assert_eq!(info.chaos, Some(true));
+    assert_eq!(info.version, None);
+
+    server.fail_next(200, r#"{"blockades": ["test"], "version": "0.4.0"}"#);
+    server.fail_next(404, "Not found");
+    let info = handler.get_daemon_info().unwrap();
+    assert_eq!(info.version, Some(String::from("0.4.0")));
+    assert_eq!(info.chaos, Some(false));
+
+    // Running chaos answers the probe outright.
+    handler.start_chaos("test", ChaosConfig::default()).unwrap();
+    assert_eq!(handler.get_daemon_info().unwrap().chaos, Some(true));
+
+    for &(status, body) in [(500, "Internal Server Error"), (401, "Unauthorized")].iter() {
+        server.fail_next(200, r#"{"blockades": ["test"]}"#);
+        server.fail_next(status, body);
+        match handler.get_daemon_info() {
+            Err(BlockadeError::ServerError(ref text)) => assert_eq!(text, body),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn start_blockade_with_driver_overrides_only_the_driver() {
+    let (server, mut handler) = mock_handler();
+    let config = three_node_config();
+    handler
+        .start_blockade_with_driver("test", config.clone(), NetDriver::Bridge)
+        .unwrap();
+    let body = server.requests()[1].body.clone();
+    let posted: BlockadeConfig = serde_json::from_str(&body).unwrap();
+    assert_eq!(posted.network.driver, NetDriver::Bridge);
+    assert_eq!(posted.containers, config.containers);
+    assert_eq!(handler.config["test"].network.driver, NetDriver::Bridge);
+}
+
+#[test]
+fn crash_loop_alternates_kill_and_start() {
+    let (server, mut handler) = started_test_blockade();
+    let state = handler
+        .crash_loop("test", "c1", 3, Duration::from_millis(1))
+        .unwrap();
+    assert_eq!(state.status, BlockadeContainerStatus::Up);
+    let actions: Vec<String> = server
+        .requests()
+        .into_iter()
+        .filter(|r| r.path.ends_with("/action"))
+        .map(|r| r.body)
+        .collect();
+    assert_eq!(actions.len(), 6);
+    assert!(actions[0].contains("kill") && actions[1].contains("start"));
+    assert!(handler.crash_loop("test", "c9", 3, Duration::from_millis(1)).is_err());
+}
+
+#[test]
+fn uncached_state_leaves_the_cache_alone() {
+    let (server, handler) = started_test_blockade();
+    BlockadeHandler::new(&server.url()).kill_container("test", "c0").unwrap();
+    let shared = Arc::new(handler);
+    let worker = shared.clone();
+    let fresh = std::thread::spawn(move || worker.get_state_uncached("test").unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(fresh.containers["c0"].status, BlockadeContainerStatus::Down);
+    assert_eq!(shared.state["test"].containers["c0"].status, BlockadeContainerStatus::Up);
+    assert!(shared.get_state_uncached("missing").is_err());
+}
+
+#[test]
+fn cached_queries_make_no_requests() {
+    let (server, handler) = started_test_blockade();
+    let sent = server.requests().len();
+    let handler = Arc::new(handler);
+    assert_eq!(handler.containers_cached("test").unwrap(), vec!["c0", "c1", "c2"]);
+    assert_eq!(handler.state_cached("test").unwrap().counts().up, 3);
+    assert!(handler.state_cached("missing").is_none());
+    assert!(handler.containers_cached("missing").is_none());
+    assert_eq!(server.requests().len(), sent);
+}
+
+#[test]
+fn weighted_selection_respects_weights() {
+    let (_server, mut handler) = started_test_blockade();
+    let mut weights = std::collections::HashMap::new();
+    weights.insert(String::from("c0"), 0.0);
+    weights.insert(String::from("c1"), 0.0);
+    for _ in 0..20 {
+        assert_eq!(handler.choose_weighted_container("test", &weights).unwrap(), "c2");
+    }
+    weights.insert(String::from("c2"), 0.0);
+    match handler.choose_weighted_container("test", &weights) {
+        Err(BlockadeError::EmptyBlockade(_)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    weights.insert(String::from("c0"), -1.0);
+    assert!(handler.choose_weighted_container("test", &weights).is_err());
+}
+
+#[test]
+fn random_net_flaky_degrades_a_fraction() {
+    let (_server, mut handler) = started_test_blockade();
+    let flaky = handler.make_random_net_flaky("test", 0.5).unwrap();
+    assert_eq!(flaky.len(), 2);
+    for (container, state) in handler.state["test"].containers.iter() {
+        let expected = if flaky.contains(container) {
+            BlockadeNetStatus::Flaky
+        } else {
+            BlockadeNetStatus::Fast
+        };
+        assert_eq!(state.network_state, expected);
+    }
+    assert!(handler.make_random_net_flaky("test", 0.0).unwrap().is_empty());
+    assert!(handler.make_random_net_flaky("test", 1.5).is_err());
+}
+
+#[test]
+fn fetch_config_answers_only_for_known_blockades() {
+    let (server, mut handler) = started_test_blockade();
+    assert_eq!(handler.fetch_config("test").unwrap(), three_node_config());
+
+    let mut other = BlockadeHandler::new(&server.url());
+    assert!(other.fetch_config("test").is_err());
+    handler.destroy_blockade("test").unwrap();
+    match handler.fetch_config("test") {
+        Err(BlockadeError::NotFound(_)) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn transitions_are_reported_on_refresh() {
+    let (server, mut handler) = mock_handler();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    handler.set_on_transition(Box::new(move |blockade, container, change| {
+        sink.lock()
+            .unwrap()
+            .push((blockade.to_owned(), container.to_owned(), change.status.clone()));
+    }));
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    assert!(seen.lock().unwrap().is_empty());
+
+    BlockadeHandler::new(&server.url()).kill_container("test", "c2").unwrap();
+    handler.fetch_state(Duration::from_secs(5)).unwrap();
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].0, "test");
+    assert_eq!(seen[0].1, "c2");
+    assert_eq!(
+        seen[0].2,
+        Some((BlockadeContainerStatus::Up, BlockadeContainerStatus::Down))
+    );
+}
+
+#[test]
+fn custom_headers_are_sent_with_every_request() {
+    let server = MockBlockadeServer::start().unwrap();
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-tenant-id", reqwest::header::HeaderValue::from_static("team-a"));
+    let mut handler = BlockadeHandler::with_headers(&server.url(), headers).unwrap();
+    handler.start_blockade("test", three_node_config(), false).unwrap();
+    for request in server.requests() {
+        assert_eq!(request.headers["x-tenant-id"], "team-a");
+    }
+}
+
+#[test]
+fn pause_network_isolates_every_container() {
+    let (_server, mut handler) = started_test_blockade();
+    handler.pause_network("test").unwrap();
+    assert_eq!(handler.state["test"].partitions().len(), 3);
+    handler.resume_network("test").unwrap();
+    assert!(!handler.state["test"].is_partitioned());
+}
+
+#[test]
+fn snapshots_ignore_volatile_fields() {
+    let (_server, handler) = started_test_blockade();
+    let options = SnapshotOptions::default();
+    let golden = serde_json::to_string(&handler.state["test"].to_snapshot(&options)).unwrap();
+    assert!(golden.starts_with(r#"{"containers":{"c0":{"status":"up""#));
+    assert!(!golden.contains("ip_address"));
+
+    let mut restarted = handler.state["test"].clone();
+    restarted.containers.get_mut("c0").unwrap().container_id = "fresh".into();
+    let expected: StateSnapshot = serde_json::from_str(&golden).unwrap();
+    assert!(restarted.matches_snapshot(&expected, &options));
+    let strict = SnapshotOptions {
+        container_id: true,
+        ..SnapshotOptions::default()
+    };
+    let before = handler.state["test"].to_snapshot(&strict);
+    assert!(!restarted.matches_snapshot(&before, &strict));
+}
+
+fn minority_fault() -> FaultSpec {
+    return FaultSpec {
+        groups: vec![
+            FaultGroup {
+                containers: vec!["c0".into()],
+                network: Some(BlockadeNetStatus::Flaky),
+            },
+            FaultGroup {
+                containers: vec!["c1".into(), "c2".into()],
+                network: None,
+            },
+        ],
+    };
+}
+
+#[test]
+fn network_fault_partitions_and_degrades() {
+    let (_server, mut handler) = started_test_blockade();
+    let applied = handler.inject_network_fault("test", minority_fault()).unwrap();
+    assert_eq!(applied, minority_fault());
+    let state = &handler.state["test"];
+    assert_eq!(state.partitions().len(), 2);
+    assert_eq!(state.containers["c0"].network_state, BlockadeNetStatus::Flaky);
+    assert_eq!(state.containers["c1"].network_state, BlockadeNetStatus::Fast);
+}
+
+/// Passes requests on to a real server but fails the second network state change.
+struct SecondNetChangeFails {
+    inner: ReqwestTransport,
+    net_changes: Mutex<usize>,
+}
+
+impl BlockadeTransport for SecondNetChangeFails {
+    fn send(
+        &self,
+        request: &reqwest::Request,
+        body: Option<&str>,
+    ) -> Result<(u16, String), BlockadeError> {
+        if request.url().path().ends_with("/network_state") {
+            let mut count = self.net_changes.lock().unwrap();
+            *count += 1;
+            if *count == 2 {
+                return Ok((500, "tc failed".into()));
+            }
+        }
+        return self.inner.send(request, body);
+    }
+}
+
+#[test]
+fn failed_network_fault_is_rolled_back() {
+    let (server, _) = started_test_blockade();
+    let transport = SecondNetChangeFails {
+        inner: ReqwestTransport::new(reqwest::Client::new()),
+        net_changes: Mutex::new(0),
+    };
+    let mut handler = BlockadeHandler::with_transport(&server.url(), Box::new(transport));
+    let mut spec = minority_fault();
+    spec.groups[1].network = Some(BlockadeNetStatus::Slow);
+    assert!(handler.inject_network_fault("test", spec).is_err());
+    let state = handler.get_state_uncached("test").unwrap();
+    assert!(!state.is_partitioned());
+    for c in state.containers.values() {
+        assert_eq!(c.network_state, BlockadeNetStatus::Fast);
+    }
+}
+
+#[test]
+fn handlers_accept_tuned_clients() {
+    let (server, _) = started_test_blockade();
+    let pooled = BlockadeHandler::with_pool_max_idle(&server.url(), 2).unwrap();
+    assert_eq!(pooled.blockades, vec!["test"]);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    let mut handler = BlockadeHandler::with_client(&server.url(), client);
+    assert_eq!(handler.state["test"].counts().up, 3);
+    handler.kill_all("test").unwrap();
+}
+
+#[test]
+fn containers_sort_by_field() {
+    let (_server, mut handler) = started_test_blockade();
+    handler.kill_container("test", "c0").unwrap();
+    handler
+        .make_partitions("test", vec![vec!["c2".into()], vec!["c0".into(), "c1".into()]])
+        .unwrap();
+    let names = |sorted: Vec<(String, BlockadeContainerState)>| -> Vec<String> {
+        return sorted.into_iter().map(|(name, _)| name).collect();
+    };
+    let by_status = handler.get_containers_sorted_by("test", ContainerSortKey::Status);
+    assert_eq!(names(by_status.unwrap()), vec!["c1", "c2", "c0"]);
+    let by_partition = handler.get_containers_sorted_by("test", ContainerSortKey::Partition);
+    assert_eq!(names(by_partition.unwrap()), vec!["c2", "c0", "c1"]);
+    let by_ip = handler.get_containers_sorted_by("test", ContainerSortKey::IpAddress);
+    assert_eq!(names(by_ip.unwrap()), vec!["c0", "c1", "c2"]);
+}
+
+#[test]
+fn unexpected_downs_are_detected() {
+    let (server, mut handler) = started_test_blockade();
+    handler.kill_container("test", "c0").unwrap();
+    BlockadeHandler::new(&server.url()).kill_container("test", "c2").unwrap();
+    let expected_up = vec![String::from("c1"), String::from("c2"), String::from("c7")];
+    assert_eq!(
+        handler.detect_unexpected_down("test", &expected_up).unwrap(),
+        vec!["c2", "c7"]
+    );
+}
+
+#[test]
+fn messages_convert_to_other_errors() {
+    let from_str: BlockadeError = "boom".into();
+    let from_string: BlockadeError = format!("boom {}", 2).into();
+    match (from_str, from_string) {
+        (BlockadeError::OtherError(a), BlockadeError::OtherError(b)) => {
+            assert_eq!(a, "boom");
+            assert_eq!(b, "boom 2");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+fn adjacency(edges: &[(&str, &[&str])]) -> Adjacency {
+    return Adjacency(
+        edges
+            .iter()
+            .map(|&(from, to)| (from.to_owned(), to.iter().map(|c| c.to_string()).collect()))
+            .collect(),
+    );
+}
+
+#[test]
+fn adjacency_translates_only_partitionable_topologies() {
+    let split = adjacency(&[("c0", &["c1"]), ("c1", &["c0"]), ("c2", &[])]);
+    assert_eq!(
+        split.to_args().unwrap(),
+        vec![vec![String::from("c0"), String::from("c1")], vec![String::from("c2")]]
+    );
+    let one_way = adjacency(&[("c0", &["c1"])]);
+    assert!(one_way.to_args().is_err());
+    let chain = adjacency(&[("c0", &["c1"]), ("c1", &["c0", "c2"]), ("c2", &["c1"])]);
+    assert!(chain.to_args().is_err());
+    let star = adjacency(&[("c0", &["c2"]), ("c1", &["c2"]), ("c2", &["c0", "c1"])]);
+    match star.to_args() {
+        Err(BlockadeError::OtherError(e)) => assert!(e.starts_with("c0 and c1 ")),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn temporary_fault_is_undone_afterwards() {
+    let (_server, mut handler) = started_test_blockade();
+    let during = handler
+        .with_temporary_fault("test", minority_fault(), Duration::from_millis(20), |h| {
+            return h.get_state_uncached("test").unwrap();
+        })
+        .unwrap();
+    assert!(during.is_partitioned());
+    assert_eq!(during.containers["c0"].network_state, BlockadeNetStatus::Flaky);
+    let after = &handler.state["test"];
+    assert!(!after.is_partitioned());
+    assert_eq!(after.containers["c0"].network_state, BlockadeNetStatus::Fast);
+}
+
+#[test]
+fn temporary_fault_is_undone_on_panic() {
+    let (_server, mut handler) = started_test_blockade();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let window = Duration::from_secs(60);
+        let _ = handler.with_temporary_fault("test", minority_fault(), window, |_| {
+            panic!("assertion inside the fault window");
+        });
+    }));
+    assert!(result.is_err());
+    let state = handler.get_state_uncached("test").unwrap();
+    assert!(!state.is_partitioned());
+    assert_eq!(state.containers["c0"].network_state, BlockadeNetStatus::Fast);
+}
+
+#[test]
+fn prune_stale_forgets_externally_destroyed_blockades() {
+    let (server, mut handler) = mock_handler();
+    handler.start_blockade("kept", three_node_config(), false).unwrap();
+    handler.start_blockade("gone", three_node_config(), false).unwrap();
+    handler.tag_containers("gone", "leader", vec!["c0".into()]);
+    BlockadeHandler::new(&server.url()).destroy_blockade("gone").unwrap();
+    assert_eq!(handler.prune_stale().unwrap(), vec!["gone"]);
+    assert!(!handler.state.contains_key("gone"));
+    assert!(!handler.config.contains_key("gone"));
+    assert!(handler.get_tagged("gone", "leader").is_err());
+    assert!(handler.state.contains_key("kept"));
+    assert!(handler.prune_stale().unwrap().is_empty());
+}
+
+#[test]
+fn reachability_follows_partitions() {
+    let (_server, mut handler) = started_test_blockade();
+    assert!(handler.can_reach("test", "c0", "c2").unwrap());
+    handler
+        .make_partitions("test", vec![vec!["c0".into()], vec!["c1".into(), "c2".into()]])
+        .unwrap();
+    assert!(!handler.can_reach("test", "c0", "c2").unwrap());
+    assert!(handler.can_reach("test", "c2", "c1").unwrap());
+    assert!(handler.can_reach("test", "c0", "c9").is_err());
+}
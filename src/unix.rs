@@ -0,0 +1,159 @@
+//! A minimal HTTP/1.1 exchange over a Unix domain socket, for daemons that don't listen on
+//! TCP.  reqwest can't use custom connectors, so requests are still prepared with reqwest
+//! (picking up auth and headers) and only written out by hand here.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+use reqwest;
+
+use blockade::{BlockadeError, BlockadeTransport};
+
+/// Sends every request over the socket at `path`; see `BlockadeHandler::with_unix_socket`.
+pub(crate) struct UnixSocketTransport {
+    path: PathBuf,
+}
+
+impl UnixSocketTransport {
+    pub(crate) fn new(path: &Path) -> Self {
+        return UnixSocketTransport {
+            path: path.to_path_buf(),
+        };
+    }
+}
+
+impl BlockadeTransport for UnixSocketTransport {
+    fn send(
+        &self,
+        request: &reqwest::Request,
+        body: Option<&str>,
+    ) -> Result<(u16, String), BlockadeError> {
+        let (status, text) = send(&self.path, request, body)?;
+        return Ok((status.as_u16(), text));
+    }
+}
+
+/// Sends `request` with `body` to the daemon listening at `socket` and returns the response
+/// status and body.  One connection is used per request.
+pub(crate) fn send(
+    socket: &Path,
+    request: &reqwest::Request,
+    body: Option<&str>,
+) -> Result<(reqwest::StatusCode, String), BlockadeError> {
+    let (status, body) = send_bytes(socket, request, body)?;
+    return Ok((status, String::from_utf8_lossy(&body).into_owned()));
+}
+
+/// Like `send`, but hands back the response body as sent, for endpoints whose answers
+/// aren't text.
+pub(crate) fn send_bytes(
+    socket: &Path,
+    request: &reqwest::Request,
+    body: Option<&str>,
+) -> Result<(reqwest::StatusCode, Vec<u8>), BlockadeError> {
+    return exchange(socket, request, body.unwrap_or("")).map_err(|e| {
+        BlockadeError::OtherError(format!("Unix socket {}: {}", socket.display(), e))
+    });
+}
+
+fn exchange(
+    socket: &Path,
+    request: &reqwest::Request,
+    body: &str,
+) -> io::Result<(reqwest::StatusCode, Vec<u8>)> {
+    let url = request.url();
+    let mut target = url.path().to_owned();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+        request.method(),
+        target,
+        body.len()
+    );
+    for (name, value) in request.headers().iter() {
+        let value = value
+            .to_str()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+    return read_response(BufReader::new(stream));
+}
+
+fn read_response<R: BufRead>(mut reader: R) -> io::Result<(reqwest::StatusCode, Vec<u8>)> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| reqwest::StatusCode::from_u16(code).ok())
+        .ok_or_else(|| invalid(format!("bad status line {:?}", status_line.trim_end())))?;
+
+    let mut length = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(idx) = line.find(':') {
+            let name = line[..idx].trim().to_lowercase();
+            let value = line[idx + 1..].trim();
+            if name == "content-length" {
+                length = value.parse::<usize>().ok();
+            } else if name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked") {
+                chunked = true;
+            }
+        }
+    }
+
+    let body = if chunked {
+        read_chunked(&mut reader)?
+    } else if let Some(n) = length {
+        let mut body = vec![0; n];
+        reader.read_exact(&mut body)?;
+        body
+    } else {
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        body
+    };
+    return Ok((status, body));
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        let size_field = size_line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size_field, 16)
+            .map_err(|_| invalid(format!("bad chunk size {:?}", size_line.trim())))?;
+        if size == 0 {
+            return Ok(body);
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        let mut crlf = String::new();
+        reader.read_line(&mut crlf)?;
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}